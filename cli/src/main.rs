@@ -30,6 +30,10 @@ enum SubCommand {
     /// though, for adding the endpoints. So might as well do everything manually.
     #[clap(version = "1.0.0")]
     setup(SetupSubcommand),
+    /// Downloads the people from the database without generating envelopes. The raw responses
+    /// are stored in a snapshot file that can later be used with `run --from-snapshot`.
+    #[clap(version = "1.0.0")]
+    fetch(FetchSubcommand),
 }
 
 /// A subcommand for cleaning files
@@ -90,6 +94,62 @@ struct RunSubcommand {
     #[allow(dead_code)]
     enable_merging_flatmates: bool,
 
+    /// Never sends usage statistics, even if `telemetry` is enabled in config.yaml.
+    #[clap(long = "no-telemetry")]
+    no_telemetry: bool,
+
+    /// Reads the people from a snapshot file created by `fetch` instead of the database.
+    /// No tokens or internet connection are required.
+    #[clap(long = "from-snapshot")]
+    from_snapshot: Option<String>,
+
+    /// Does not generate the pdf, but shows which group is printed for each person, with the
+    /// priority from group_priorities.yaml that decided it.
+    #[clap(long = "dry-run")]
+    dry_run: bool,
+
+    /// The order of the envelopes, as comma separated keys: group, name, zip, town. Later keys
+    /// decide where the earlier ones are equal, e.g. `--sort zip,group,name`.
+    #[clap(long = "sort", default_value = "group")]
+    sort: combine::SortOrder,
+
+    /// Marks where the group of the first receiver changes: none, page (a separator page with the
+    /// group name and the number of envelopes) or marker (a coloured triangle on the left edge of
+    /// every envelope).
+    #[clap(long = "group-separator", default_value = "none")]
+    group_separator: combine::GroupSeparator,
+
+    /// The envelope format: C4, C5, C6, C6/5, DL, or a size in mm like 220x110. Overrides the
+    /// `envelope` section of config.yaml. C5 if neither is set.
+    #[clap(long = "envelope")]
+    envelope: Option<combine::EnvelopeFormat>,
+
+    /// Prints address labels instead of envelopes, into output_etiketten.pdf. The label sheet is
+    /// L7160, L7163, 3475, 3422 (Avery Zweckform), or config for the `labels` section of
+    /// config.yaml.
+    #[clap(long = "labels", conflicts_with = "letter")]
+    labels: Option<combine::LabelFormat>,
+
+    /// How many labels of the first sheet are already used. They are left empty.
+    #[clap(long = "label-offset", default_value = "0")]
+    label_offset: usize,
+
+    /// Prints A4 letters for window envelopes instead of envelopes, into output_briefe.pdf, with
+    /// the address where the window is: left or right.
+    #[clap(long = "letter")]
+    letter: Option<combine::WindowPosition>,
+
+    /// A text file with the content of the letters. `# ` starts a heading, `- ` a bullet point,
+    /// and placeholders like {nicknames}, {groups} or {name} are filled in for every letter.
+    #[clap(long = "letter-template", requires = "letter")]
+    letter_template: Option<String>,
+}
+
+#[derive(Clap)]
+struct FetchSubcommand {
+    /// Where to store the snapshot. It contains personal data, so treat it like a member list.
+    #[clap(long = "save-snapshot", default_value = "snapshot.json")]
+    save_snapshot: String,
 }
 
 #[derive(Clap)]
@@ -122,10 +182,37 @@ fn main() {
         SubCommand::run(c) => {
             ui.inform_user("Running...");
 
-            combine::main(&ui, &combine::PrintingParameters::new().print_sidebadges(!c.disable_sidebadges).print_groups(!c.disable_groups).print_names(!c.disable_nicknames).merge_flatmates(!c.disable_merging_flatmates));
+            // read before the database is asked, so that mistakes show up right away
+            let letter_template = c.letter_template.as_ref().map(|path| {
+                let template = std::fs::read_to_string(path)
+                    .map_err(|e| e.to_string())
+                    .and_then(|text| text.parse::<combine::LetterTemplate>());
+                template.unwrap_or_else(|reason| {
+                    let e = dbparse::Error::Config {
+                        filename: path.clone(),
+                        reason: reason,
+                    };
+                    ui.error_dbparse(&e);
+                    std::process::exit(e.exit_code());
+                })
+            });
+
+            let result = combine::main(&ui, &combine::PrintingParameters::new().print_sidebadges(!c.disable_sidebadges).print_groups(!c.disable_groups).print_names(!c.disable_nicknames).merge_flatmates(!c.disable_merging_flatmates).from_snapshot(c.from_snapshot).telemetry(!c.no_telemetry).dry_run(c.dry_run).sort_order(c.sort).group_separator(c.group_separator).envelope_format(c.envelope).label_format(c.labels).label_offset(c.label_offset).letter_window(c.letter).letter_template(letter_template));
+            // the error has already been shown to the user
+            if let Err(e) = result {
+                std::process::exit(e.exit_code());
+            }
 
             ui.inform_user("Done. If above output looks problematic - check the output pdf anyway. Perhaps the program fixed everything on its own.");
         },
+        SubCommand::fetch(f) => {
+            ui.inform_user("Fetching...");
+            match combine::fetch_snapshot(&ui, &f.save_snapshot) {
+                Ok(pages) => ui.inform_user(&format!("Stored {} pages in {}.", pages, f.save_snapshot)),
+                // the error has already been shown to the user
+                Err(e) => std::process::exit(e.exit_code()),
+            }
+        },
         SubCommand::setup(s) => {
             let mut email: Option<String> = s.email.clone();
             let api_token = if s.service_token.is_none()
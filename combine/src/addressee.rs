@@ -0,0 +1,171 @@
+use chrono::Datelike;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// To whom a couvert is addressed. Mail for children should go to their parents, but the Cevi name
+// of the child is still printed at the top of the couvert, so that it is clear who it is for.
+//
+// The strategy is chosen per role_type in roles.yaml under `addressees`. Role types that are not
+// listed there are addressed to the person. If someone has several roles, the least parental
+// strategy wins, so that a 17 year old leader who is also Teilnehmer somewhere still gets their own
+// mail.
+
+/// people younger than this are minors
+pub const AGE_OF_MAJORITY: i32 = 18;
+
+/// ordered from the least to the most parental strategy
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Addressee {
+    /// "Anna Muster"
+    Person,
+    /// "Familie Muster"
+    Family,
+    /// `Parents` for people younger than 18, `Person` for everyone else and for people whose
+    /// birthday is unknown
+    ParentsIfMinor,
+    /// "Herr und Frau Hans und Maria Muster", from `salutation_parents` and `name_parents`
+    Parents,
+}
+
+/// the strategies that are shipped with the tool
+pub fn default_addressees() -> BTreeMap<String, Addressee> {
+    let mut addressees = BTreeMap::new();
+    addressees.insert(String::from("Teilnehmer/-in"), Addressee::ParentsIfMinor);
+    addressees
+}
+
+/// How `person` is addressed, never `ParentsIfMinor`. `today` is the date the age is computed for.
+pub fn addressee_of(
+    person: &dbparse::ReasonablePerson,
+    addressees: &BTreeMap<String, Addressee>,
+    today: chrono::NaiveDate,
+) -> Addressee {
+    let strategy = person
+        .roles
+        .iter()
+        .map(|role| {
+            addressees
+                .get(&role.role_type)
+                .cloned()
+                .unwrap_or(Addressee::Person)
+        })
+        .min()
+        .unwrap_or(Addressee::Person);
+    match strategy {
+        Addressee::ParentsIfMinor => match age(&person.birthday, today) {
+            Some(age) if age < AGE_OF_MAJORITY => Addressee::Parents,
+            _ => Addressee::Person,
+        },
+        other => other,
+    }
+}
+
+/// full years between `birthday` ("YYYY-MM-DD") and `today`, `None` if the birthday is unknown
+fn age(birthday: &str, today: chrono::NaiveDate) -> Option<i32> {
+    let birthday = chrono::NaiveDate::parse_from_str(birthday.trim(), "%Y-%m-%d").ok()?;
+    let mut age = today.year() - birthday.year();
+    if (today.month(), today.day()) < (birthday.month(), birthday.day()) {
+        age -= 1;
+    }
+    return Some(age);
+}
+
+/// "Herr und Frau Hans und Maria Muster". If the database does not know the names of the parents,
+/// "Herr und Frau Muster", or "Familie Muster" if it does not know the salutation either.
+pub fn parents_line(person: &dbparse::ReasonablePerson) -> String {
+    let salutation = person.salutation_parents.trim();
+    let names = person.name_parents.trim();
+    if !names.is_empty() {
+        return format!("{} {}", salutation, names).trim().to_string();
+    }
+    if !salutation.is_empty() {
+        return format!("{} {}", salutation, person.last_name);
+    }
+    return format!("Familie {}", person.last_name);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Addressee;
+
+    fn date(date: &str) -> chrono::NaiveDate {
+        chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap()
+    }
+
+    fn person(role_types: &[&str], birthday: &str) -> dbparse::ReasonablePerson {
+        let mut person = dbparse::ReasonablePerson {
+            id: 1,
+            first_name: String::from("Anna"),
+            last_name: String::from("Muster"),
+            nickname: String::from("Fiona"),
+            address: dbparse::PostalAddress::parse("Hauptstrasse 5", "8330", "Pfäffikon ZH"),
+            salutation_parents: String::from("Herr und Frau"),
+            name_parents: String::from("Hans und Maria Muster"),
+            birthday: String::from(birthday),
+            ..dbparse::ReasonablePerson::default()
+        };
+        for (i, role_type) in role_types.iter().enumerate() {
+            person.roles.insert(dbparse::Role::new(
+                std::rc::Rc::<str>::from(i.to_string().as_str()),
+                String::from(*role_type),
+                None,
+                String::from("1341"),
+                String::from("115"),
+            ));
+        }
+        person
+    }
+
+    #[test]
+    fn test_minor_teilnehmer_is_addressed_to_parents() {
+        let addressees = super::default_addressees();
+        let today = date("2026-04-01");
+        let cases = vec![
+            ("2008-04-02", Addressee::Parents),
+            ("2008-04-01", Addressee::Person),
+            ("2000-01-01", Addressee::Person),
+            ("", Addressee::Person),
+            ("unbekannt", Addressee::Person),
+        ];
+        for (birthday, expected) in cases {
+            assert_eq!(
+                super::addressee_of(&person(&["Teilnehmer/-in"], birthday), &addressees, today),
+                expected,
+                "{}",
+                birthday
+            );
+        }
+    }
+
+    #[test]
+    fn test_least_parental_role_wins() {
+        let addressees = super::default_addressees();
+        let today = date("2026-04-01");
+        let leader = person(&["Teilnehmer/-in", "Minigruppenleiter/-in"], "2010-01-01");
+        assert_eq!(
+            super::addressee_of(&leader, &addressees, today),
+            Addressee::Person
+        );
+
+        let mut family_addressees = addressees.clone();
+        family_addressees.insert(String::from("Mitglied"), Addressee::Family);
+        let member = person(&["Mitglied"], "");
+        assert_eq!(
+            super::addressee_of(&member, &family_addressees, today),
+            Addressee::Family
+        );
+    }
+
+    #[test]
+    fn test_parents_line_fallbacks() {
+        let mut anna = person(&[], "");
+        assert_eq!(
+            super::parents_line(&anna),
+            "Herr und Frau Hans und Maria Muster"
+        );
+        anna.name_parents = String::new();
+        assert_eq!(super::parents_line(&anna), "Herr und Frau Muster");
+        anna.salutation_parents = String::new();
+        assert_eq!(super::parents_line(&anna), "Familie Muster");
+    }
+}
@@ -0,0 +1,204 @@
+// How an address is written depends on the country it goes to. The couverts are posted in
+// Switzerland, so Swiss addresses get no country line, and all others get the name of the country
+// in capital letters as the last line, as recommended by the Swiss Post.
+//
+// The database delivers the country either as ISO code ("DE") or as name ("Deutschland"), so
+// every rule knows a few aliases.
+
+/// how the zip code and the town are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TownLine {
+    /// "8330 Pfäffikon ZH"
+    ZipTown,
+    /// "Springfield IL 62701"
+    TownZip,
+    /// "London" and then "SW1A 1AA" on a line of its own
+    TownThenZip,
+}
+
+struct CountryRule {
+    /// the ISO code first, then names in upper case
+    aliases: &'static [&'static str],
+    /// `None` for the home country
+    country_line: Option<&'static str>,
+    town_line: TownLine,
+}
+
+const HOME_COUNTRY: CountryRule = CountryRule {
+    aliases: &["CH", "SCHWEIZ", "SUISSE", "SVIZZERA", "SWITZERLAND"],
+    country_line: None,
+    town_line: TownLine::ZipTown,
+};
+
+const FOREIGN_COUNTRIES: &[CountryRule] = &[
+    CountryRule {
+        aliases: &["LI", "FL", "LIECHTENSTEIN"],
+        country_line: Some("LIECHTENSTEIN"),
+        town_line: TownLine::ZipTown,
+    },
+    CountryRule {
+        aliases: &["DE", "DEUTSCHLAND", "GERMANY", "ALLEMAGNE"],
+        country_line: Some("DEUTSCHLAND"),
+        town_line: TownLine::ZipTown,
+    },
+    CountryRule {
+        aliases: &["AT", "ÖSTERREICH", "OESTERREICH", "AUSTRIA", "AUTRICHE"],
+        country_line: Some("ÖSTERREICH"),
+        town_line: TownLine::ZipTown,
+    },
+    CountryRule {
+        aliases: &["FR", "FRANKREICH", "FRANCE"],
+        country_line: Some("FRANKREICH"),
+        town_line: TownLine::ZipTown,
+    },
+    CountryRule {
+        aliases: &["IT", "ITALIEN", "ITALIA", "ITALY", "ITALIE"],
+        country_line: Some("ITALIEN"),
+        town_line: TownLine::ZipTown,
+    },
+    CountryRule {
+        aliases: &["NL", "NIEDERLANDE", "NEDERLAND", "NETHERLANDS", "PAYS-BAS"],
+        country_line: Some("NIEDERLANDE"),
+        town_line: TownLine::ZipTown,
+    },
+    CountryRule {
+        aliases: &["GB", "UK", "GROSSBRITANNIEN", "UNITED KINGDOM", "ROYAUME-UNI"],
+        country_line: Some("GROSSBRITANNIEN"),
+        town_line: TownLine::TownThenZip,
+    },
+    CountryRule {
+        aliases: &["US", "USA", "VEREINIGTE STAATEN", "UNITED STATES", "ETATS-UNIS"],
+        country_line: Some("USA"),
+        town_line: TownLine::TownZip,
+    },
+];
+
+/// the rule for the country of `address`. `None` means the country is not in the list.
+fn rule_for(address: &dbparse::PostalAddress) -> Option<&'static CountryRule> {
+    match &address.country {
+        None => Some(&HOME_COUNTRY),
+        Some(country) => rule_named(country),
+    }
+}
+
+fn rule_named(country: &str) -> Option<&'static CountryRule> {
+    let country = country.trim().to_uppercase();
+    std::iter::once(&HOME_COUNTRY)
+        .chain(FOREIGN_COUNTRIES.iter())
+        .find(|rule| rule.aliases.contains(&country.as_str()))
+}
+
+/// the same for all aliases of a country, e.g. "CH" for "Schweiz"
+fn country_key(country: &str) -> String {
+    match rule_named(country) {
+        Some(rule) => rule.aliases[0].to_string(),
+        None => country.trim().to_uppercase(),
+    }
+}
+
+/// Whether both addresses name their country and these are not the same, however they are
+/// spelled. An address without a country could be anywhere, so it never differs.
+pub fn countries_differ(a: &dbparse::PostalAddress, b: &dbparse::PostalAddress) -> bool {
+    match (&a.country, &b.country) {
+        (Some(x), Some(y)) => country_key(x) != country_key(y),
+        _ => false,
+    }
+}
+
+/// the lines with zip code and town, in the order of the destination country
+pub fn town_lines(address: &dbparse::PostalAddress) -> Vec<String> {
+    let town_line = rule_for(address)
+        .map(|rule| rule.town_line)
+        .unwrap_or(TownLine::ZipTown);
+    let lines = match town_line {
+        TownLine::ZipTown => vec![format!("{} {}", address.zip_code, address.town)],
+        TownLine::TownZip => vec![format!("{} {}", address.town, address.zip_code)],
+        TownLine::TownThenZip => vec![address.town.clone(), address.zip_code.clone()],
+    };
+    lines
+        .into_iter()
+        .map(|line| line.trim().to_string())
+        .filter(|line| !line.is_empty())
+        .collect()
+}
+
+/// the last line of the address, `None` for Switzerland. Countries that are not in the list are
+/// printed as the database delivers them, in upper case.
+pub fn country_line(address: &dbparse::PostalAddress) -> Option<String> {
+    match rule_for(address) {
+        Some(rule) => rule.country_line.map(String::from),
+        None => address
+            .country
+            .as_ref()
+            .map(|country| country.trim().to_uppercase()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    fn address(country: Option<&str>) -> dbparse::PostalAddress {
+        let mut address = dbparse::PostalAddress::parse("Hauptstrasse 5", "8330", "Pfäffikon ZH");
+        address.country = country.map(String::from);
+        address
+    }
+
+    #[test]
+    fn test_switzerland_has_no_country_line() {
+        for country in vec![None, Some("CH"), Some("Schweiz"), Some(" ch ")] {
+            assert_eq!(super::country_line(&address(country)), None, "{:?}", country);
+            assert_eq!(
+                super::town_lines(&address(country)),
+                vec!["8330 Pfäffikon ZH"]
+            );
+        }
+    }
+
+    #[test]
+    fn test_neighbouring_countries() {
+        assert_eq!(
+            super::country_line(&address(Some("DE"))),
+            Some(String::from("DEUTSCHLAND"))
+        );
+        assert_eq!(
+            super::country_line(&address(Some("Liechtenstein"))),
+            Some(String::from("LIECHTENSTEIN"))
+        );
+        assert_eq!(
+            super::country_line(&address(Some("France"))),
+            Some(String::from("FRANKREICH"))
+        );
+    }
+
+    #[test]
+    fn test_town_line_order() {
+        let mut london = address(Some("GB"));
+        london.zip_code = String::from("SW1A 1AA");
+        london.town = String::from("London");
+        assert_eq!(super::town_lines(&london), vec!["London", "SW1A 1AA"]);
+
+        let mut springfield = address(Some("US"));
+        springfield.zip_code = String::from("62701");
+        springfield.town = String::from("Springfield IL");
+        assert_eq!(super::town_lines(&springfield), vec!["Springfield IL 62701"]);
+    }
+
+    #[test]
+    fn test_countries_differ() {
+        let differ = |a, b| super::countries_differ(&address(a), &address(b));
+        assert!(!differ(Some("CH"), Some("Schweiz")));
+        assert!(!differ(Some("DE"), Some("deutschland")));
+        assert!(!differ(Some("Norwegen"), Some("NORWEGEN")));
+        assert!(!differ(None, Some("DE")));
+        assert!(differ(Some("CH"), Some("DE")));
+        assert!(differ(Some("Norwegen"), Some("Schweden")));
+    }
+
+    #[test]
+    fn test_unknown_country_is_printed_as_is() {
+        assert_eq!(
+            super::country_line(&address(Some("Norwegen"))),
+            Some(String::from("NORWEGEN"))
+        );
+        assert_eq!(super::town_lines(&address(Some("Norwegen"))), vec!["8330 Pfäffikon ZH"]);
+    }
+}
@@ -0,0 +1,310 @@
+use serde::{Deserialize, Serialize};
+use std::fs::{read_to_string, OpenOptions};
+use std::io::Write;
+
+// Some people must not get a couvert, even though they are in the database: they opted out, or
+// they moved away and the database was never updated. exclude.yaml lists them, so that they can
+// be dropped before the households are put together, without editing the database.
+//
+// A person is excluded by their hitobito id, by their name together with their address, or because
+// they are in an excluded group. Every entry has a reason, which is shown in the report next to
+// the people it dropped.
+
+pub const EXCLUDE_YAML_FILE: &str = "exclude.yaml";
+const EXCLUDE_YAML_FILE_TEMPLATE: &str = r###"---
+# Personen, die kein Couvert erhalten, obwohl sie in der Datenbank sind.
+# Entweder mit ihrer hitobito id, oder mit Vorname, Nachname und Adresse (Postleitzahl optional).
+people: []
+# z.B.:
+# people:
+#   - id: 1234
+#     reason: möchte keine Post mehr
+#   - first_name: Anna
+#     last_name: Muster
+#     address: Hauptstrasse 5
+#     zip_code: "8330"
+#     reason: ins Ausland gezogen
+
+# Gruppen, deren Mitglieder kein Couvert erhalten. Die ids stehen in mapping.yaml.
+groups: []
+# z.B.:
+# groups:
+#   - id: 4321
+#     reason: Z_Import Optigem
+
+# Diese Datei wird neu erstellt, wenn sie gelöscht wird.
+"###;
+
+/// the content of exclude.yaml
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ExclusionList {
+    #[serde(default)]
+    pub people: Vec<ExcludedPerson>,
+    #[serde(default)]
+    pub groups: Vec<ExcludedGroup>,
+}
+
+/// Matches by `id` if it is set. Otherwise, the names and the address must all match.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ExcludedPerson {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub first_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_name: Option<String>,
+    /// the street line, e.g. "Hauptstrasse 5". Spelling variants like "Hauptstr. 5" match too.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub zip_code: Option<String>,
+    #[serde(default)]
+    pub reason: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ExcludedGroup {
+    pub id: usize,
+    #[serde(default)]
+    pub reason: String,
+}
+
+/// what `apply` did
+#[derive(Debug, Default)]
+pub struct Exclusions {
+    /// every person that was dropped, with the reason of the first entry that matched
+    pub excluded: Vec<(dbparse::ReasonablePerson, String)>,
+    /// entries of exclude.yaml that did not match anybody, or that cannot match anybody
+    pub unused_entries: Vec<String>,
+}
+
+impl ExcludedPerson {
+    /// entries without id need a name and an address, so that they do not drop namesakes
+    fn is_valid(&self) -> bool {
+        self.id.is_some()
+            || (self.first_name.is_some() && self.last_name.is_some() && self.address.is_some())
+    }
+
+    fn matches(&self, person: &dbparse::ReasonablePerson) -> bool {
+        if let Some(id) = self.id {
+            return id == person.id;
+        }
+        let (first_name, last_name, address) =
+            match (&self.first_name, &self.last_name, &self.address) {
+                (Some(first_name), Some(last_name), Some(address)) => {
+                    (first_name, last_name, address)
+                },
+                _ => return false,
+            };
+        let zip_code = match &self.zip_code {
+            Some(zip_code) => zip_code.as_str(),
+            None => &person.address.zip_code,
+        };
+        let mut excluded_address =
+            dbparse::PostalAddress::parse(address, zip_code, &person.address.town);
+        if excluded_address.postbox.is_empty() {
+            excluded_address.postbox = person.address.postbox.clone();
+        }
+        same_name(first_name, &person.first_name)
+            && same_name(last_name, &person.last_name)
+            && excluded_address.same_household(&person.address)
+    }
+
+    fn describe(&self) -> String {
+        match self.id {
+            Some(id) => format!("id {}", id),
+            None => [&self.first_name, &self.last_name, &self.address]
+                .iter()
+                .filter_map(|part| part.as_ref().map(String::as_str))
+                .collect::<Vec<&str>>()
+                .join(" "),
+        }
+    }
+}
+
+fn same_name(a: &str, b: &str) -> bool {
+    a.trim().to_lowercase() == b.trim().to_lowercase()
+}
+
+/// Loads exclude.yaml. If it does not exist yet, a template is created and nobody is excluded.
+///
+/// If exclude.yaml cannot be parsed, it is left untouched and nobody is excluded.
+pub fn load_exclusion_list(user_interface: &dyn ui::UserInteractor) -> ExclusionList {
+    match read_to_string(EXCLUDE_YAML_FILE) {
+        Ok(yaml) => match serde_yaml::from_str::<ExclusionList>(&yaml) {
+            Ok(list) => list,
+            Err(e) => {
+                user_interface.error_loading_exclusions(&e);
+                ExclusionList::default()
+            },
+        },
+        Err(_e) => {
+            println!("combine: no {} yet. Creating it...", EXCLUDE_YAML_FILE);
+            if let Err(e) = create_exclude_yaml_file_template() {
+                user_interface.error_loading_exclusions(&e);
+            }
+            ExclusionList::default()
+        },
+    }
+}
+
+pub fn create_exclude_yaml_file_template() -> Result<(), std::io::Error> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(EXCLUDE_YAML_FILE)?;
+    file.write_all(EXCLUDE_YAML_FILE_TEMPLATE.as_bytes())?;
+    Ok(())
+}
+
+/// removes everybody from `people` who is matched by an entry of `list`
+pub fn apply(people: &mut Vec<dbparse::ReasonablePerson>, list: &ExclusionList) -> Exclusions {
+    let mut exclusions = Exclusions::default();
+    let mut person_entry_used = vec![false; list.people.len()];
+    let mut group_entry_used = vec![false; list.groups.len()];
+
+    let mut kept: Vec<dbparse::ReasonablePerson> = Vec::with_capacity(people.len());
+    for person in people.drain(..) {
+        let by_person = list.people.iter().position(|entry| entry.matches(&person));
+        let by_group = list.groups.iter().position(|entry| {
+            person
+                .groups
+                .iter()
+                .any(|group| group.inner_group.id == entry.id.to_string())
+        });
+        if let Some(i) = by_group {
+            group_entry_used[i] = true;
+        }
+        let reason = match (by_person, by_group) {
+            (Some(i), _) => {
+                person_entry_used[i] = true;
+                list.people[i].reason.clone()
+            },
+            (None, Some(i)) => {
+                let group_name = person
+                    .groups
+                    .iter()
+                    .find(|group| group.inner_group.id == list.groups[i].id.to_string())
+                    .map(|group| group.inner_group.name.clone())
+                    .unwrap_or_default();
+                format!("Gruppe {}: {}", group_name, list.groups[i].reason)
+            },
+            (None, None) => {
+                kept.push(person);
+                continue;
+            },
+        };
+        exclusions.excluded.push((person, reason));
+    }
+    *people = kept;
+
+    for (entry, used) in list.people.iter().zip(person_entry_used) {
+        if !entry.is_valid() {
+            exclusions.unused_entries.push(format!(
+                "{}: braucht eine id, oder Vorname, Nachname und Adresse",
+                entry.describe()
+            ));
+        } else if !used {
+            exclusions
+                .unused_entries
+                .push(format!("{}: trifft auf niemanden zu", entry.describe()));
+        }
+    }
+    for (entry, used) in list.groups.iter().zip(group_entry_used) {
+        if !used {
+            exclusions
+                .unused_entries
+                .push(format!("Gruppe {}: trifft auf niemanden zu", entry.id));
+        }
+    }
+    return exclusions;
+}
+
+#[cfg(test)]
+mod tests {
+    fn person(id: usize, first_name: &str, street_line: &str) -> dbparse::ReasonablePerson {
+        dbparse::ReasonablePerson {
+            id: id,
+            first_name: String::from(first_name),
+            last_name: String::from("Muster"),
+            address: dbparse::PostalAddress::parse(street_line, "8330", "Pfäffikon ZH"),
+            ..dbparse::ReasonablePerson::default()
+        }
+    }
+
+    fn ids(people: &Vec<dbparse::ReasonablePerson>) -> Vec<usize> {
+        people.iter().map(|person| person.id).collect()
+    }
+
+    #[test]
+    fn test_exclude_by_id_and_by_name_and_address() {
+        let mut people = vec![
+            person(1, "Anna", "Hauptstrasse 5"),
+            person(2, "Beat", "Hauptstrasse 5"),
+            person(3, "Carla", "Hauptstrasse 5"),
+            person(4, "Beat", "Dorfplatz 1"),
+        ];
+        let list: super::ExclusionList = serde_yaml::from_str(
+            r###"---
+people:
+  - id: 1
+    reason: möchte keine Post mehr
+  - first_name: beat
+    last_name: Muster
+    address: Hauptstr. 5
+    reason: weggezogen
+  - id: 99
+    reason: gibt es nicht
+  - last_name: Muster
+    reason: zu ungenau
+"###,
+        )
+        .unwrap();
+
+        let exclusions = super::apply(&mut people, &list);
+
+        assert_eq!(ids(&people), vec![3, 4]);
+        let excluded: Vec<(usize, &str)> = exclusions
+            .excluded
+            .iter()
+            .map(|(person, reason)| (person.id, reason.as_str()))
+            .collect();
+        assert_eq!(
+            excluded,
+            vec![(1, "möchte keine Post mehr"), (2, "weggezogen")]
+        );
+        assert_eq!(exclusions.unused_entries.len(), 2);
+    }
+
+    #[test]
+    fn test_exclude_by_group() {
+        let mut anna = person(1, "Anna", "Hauptstrasse 5");
+        anna.groups.insert(dbparse::ReasonableGroup {
+            inner_group: dbparse::Group {
+                id: String::from("4321"),
+                name: String::from("Z_Import Optigem"),
+                group_type: String::from("Gruppe"),
+            },
+        });
+        let mut people = vec![anna, person(2, "Beat", "Hauptstrasse 5")];
+        let list: super::ExclusionList =
+            serde_yaml::from_str("groups:\n  - id: 4321\n    reason: nicht mehr aktuell\n")
+                .unwrap();
+
+        let exclusions = super::apply(&mut people, &list);
+
+        assert_eq!(ids(&people), vec![2]);
+        assert_eq!(
+            exclusions.excluded[0].1,
+            "Gruppe Z_Import Optigem: nicht mehr aktuell"
+        );
+        assert!(exclusions.unused_entries.is_empty());
+    }
+
+    #[test]
+    fn test_template_is_valid() {
+        let list: super::ExclusionList =
+            serde_yaml::from_str(super::EXCLUDE_YAML_FILE_TEMPLATE).unwrap();
+        assert_eq!(list, super::ExclusionList::default());
+    }
+}
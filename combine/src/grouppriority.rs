@@ -0,0 +1,243 @@
+use dbparse;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::read_to_string;
+use std::io::Write;
+
+// A person is usually in several groups, but only one of them is printed on the couvert: the one
+// with the highest priority. group_priorities.yaml is stored next to mapping.yaml and decides
+// which one that is.
+//
+// Priorities are looked up in this order:
+// 1. `group_ids`: a specific group of your Abteilung, by its id (see mapping.yaml for the ids)
+// 2. `group_names`: a group by its name as it is stored in the database
+// 3. `group_types`: every group of that type
+// 4. `default`
+
+pub const GROUP_PRIORITIES_YAML_FILE: &str = "group_priorities.yaml";
+
+/// the content of group_priorities.yaml
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GroupPriorities {
+    #[serde(default)]
+    pub group_ids: BTreeMap<String, i32>,
+    #[serde(default)]
+    pub group_names: BTreeMap<String, i32>,
+    #[serde(default)]
+    pub group_types: BTreeMap<String, i32>,
+    /// for group types that are not listed. If something is not in the list, we don't want it in
+    /// almost all cases
+    #[serde(default)]
+    pub default: i32,
+}
+
+impl Default for GroupPriorities {
+    /// Higher priority for more specific groups
+    /// I try to use distinct priorities, so that the same dataset will always produce the same
+    /// output. That does not hold for groups that fall back to the default.
+    fn default() -> Self {
+        let group_types = vec![
+            ("Dachverband", 10),
+            ("Mitgliederorganisation", 30),
+            ("Sektion", 40),
+            ("Verein", 45),
+            ("Jungschar", 49),
+            ("Gruppe", 50),     // M oder F
+            ("Ortsgruppe", 60), // Pfä-Feh-Hi-Rus
+            ("Stufe", 70),
+            ("Mitglieder", 71),
+            //--- end useless stuff ---
+            ("Ten-Sing", 80),
+            ("Gremium", 81), // e.g. "C-Gruppe", "Cevi Plus Team"
+            ("Externe", 83),
+            ("Vorstand", 90), // if somebody is in a group and in vorstand, we want the group
+            ("Untergruppe", 100), // We want always this. It's e.g. Holon.
+            ("Fröschli", 100), // Whyever this exists
+        ];
+        // groups of type "Externe" that exist in most Abteilungen
+        let group_names = vec![
+            ("Trägerkreis Mitglieder", 89),
+            ("J+S-Coaches", 88),
+            ("Leiter ehemalig", 87),
+            ("Ehemalige", 87),
+            ("Gebetsbrunch", 86),
+            ("C-Newsletter", 85),
+            ("Freie Mitarbeiter", 84),
+            // technical groups that nobody wants on their couvert
+            ("Z_Import Optigem", 1),
+            ("Admin GS 2019", 3),
+            ("EXT: Y-Card Aktiv und Gültig", 2),
+        ];
+        GroupPriorities {
+            group_ids: BTreeMap::new(),
+            group_names: group_names
+                .into_iter()
+                .map(|(name, priority)| (name.to_string(), priority))
+                .collect(),
+            group_types: group_types
+                .into_iter()
+                .map(|(group_type, priority)| (group_type.to_string(), priority))
+                .collect(),
+            default: 0,
+        }
+    }
+}
+
+/// where a resolved priority came from, for the dry-run report
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PrioritySource {
+    GroupId,
+    GroupName,
+    GroupType,
+    Default,
+}
+impl fmt::Display for PrioritySource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                PrioritySource::GroupId => "group_ids",
+                PrioritySource::GroupName => "group_names",
+                PrioritySource::GroupType => "group_types",
+                PrioritySource::Default => "default",
+            }
+        )
+    }
+}
+
+impl GroupPriorities {
+    pub fn resolve(&self, group: &dbparse::ReasonableGroup) -> (i32, PrioritySource) {
+        let group = &group.inner_group;
+        if let Some(priority) = self.group_ids.get(&group.id) {
+            return (*priority, PrioritySource::GroupId);
+        }
+        if let Some(priority) = self.group_names.get(&group.name) {
+            return (*priority, PrioritySource::GroupName);
+        }
+        if let Some(priority) = self.group_types.get(&group.group_type) {
+            return (*priority, PrioritySource::GroupType);
+        }
+        (self.default, PrioritySource::Default)
+    }
+
+    pub fn priority(&self, group: &dbparse::ReasonableGroup) -> i32 {
+        self.resolve(group).0
+    }
+}
+
+/// Loads group_priorities.yaml, adds every group type of `groups` that is not yet in there with
+/// the default priority and stores the result again, so that the user sees all group types.
+/// The shipped priorities are only written when the file is created, so entries the user deleted
+/// stay deleted.
+///
+/// If group_priorities.yaml cannot be parsed, it is left untouched and the shipped defaults are
+/// used.
+pub fn load_group_priorities<'a>(
+    groups: impl Iterator<Item = &'a dbparse::ReasonableGroup>,
+    user_interface: &dyn ui::UserInteractor,
+) -> GroupPriorities {
+    let loaded: GroupPriorities = match read_to_string(GROUP_PRIORITIES_YAML_FILE) {
+        Ok(yaml) => match serde_yaml::from_str::<GroupPriorities>(&yaml) {
+            Ok(priorities) => priorities,
+            Err(e) => {
+                user_interface.error_loading_group_priorities(&e);
+                return GroupPriorities::default();
+            },
+        },
+        Err(_e) => {
+            println!(
+                "combine: no {} yet. Creating it...",
+                GROUP_PRIORITIES_YAML_FILE
+            );
+            GroupPriorities::default()
+        },
+    };
+    let mut priorities = loaded;
+    for group in groups {
+        let default = priorities.default;
+        priorities
+            .group_types
+            .entry(group.inner_group.group_type.clone())
+            .or_insert(default);
+    }
+
+    let stored = serde_yaml::to_string(&priorities)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
+        .and_then(|yaml| {
+            std::fs::File::create(GROUP_PRIORITIES_YAML_FILE)?.write_all(yaml.as_bytes())
+        });
+    if let Err(e) = stored {
+        user_interface.error_writing_file(GROUP_PRIORITIES_YAML_FILE, &e);
+    }
+
+    priorities
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GroupPriorities, PrioritySource};
+
+    fn group(id: &str, name: &str, group_type: &str) -> dbparse::ReasonableGroup {
+        dbparse::ReasonableGroup::from(dbparse::Group {
+            id: id.to_string(),
+            name: name.to_string(),
+            group_type: group_type.to_string(),
+        })
+    }
+
+    #[test]
+    fn test_group_type_priority() {
+        let priorities = GroupPriorities::default();
+        let holon = group("1341", "Holon (M)", "Untergruppe");
+        let stufe = group("1", "Stufe", "Stufe");
+        assert!(priorities.priority(&holon) > priorities.priority(&stufe));
+        assert_eq!(priorities.resolve(&holon).1, PrioritySource::GroupType);
+    }
+
+    #[test]
+    fn test_group_id_overrides_everything() {
+        let mut priorities = GroupPriorities::default();
+        priorities.group_ids.insert(String::from("2298"), 5);
+        let admin = group("2298", "Admin GS 2019", "Externe");
+        assert_eq!(priorities.resolve(&admin), (5, PrioritySource::GroupId));
+        let other_admin = group("9999", "Admin GS 2019", "Externe");
+        assert_eq!(
+            priorities.resolve(&other_admin),
+            (3, PrioritySource::GroupName)
+        );
+        let other_externe = group("9998", "Lagerküche", "Externe");
+        assert_eq!(
+            priorities.resolve(&other_externe),
+            (83, PrioritySource::GroupType)
+        );
+    }
+
+    #[test]
+    fn test_technical_groups_lose_against_stufe() {
+        let priorities = GroupPriorities::default();
+        let stufe = group("1", "Senshi (M)", "Stufe");
+        for name in vec![
+            "Z_Import Optigem",
+            "Admin GS 2019",
+            "EXT: Y-Card Aktiv und Gültig",
+        ] {
+            let technical = group("2", name, "Externe");
+            assert!(
+                priorities.priority(&technical) < priorities.priority(&stufe),
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_unknown_group_type_gets_default() {
+        let priorities: GroupPriorities = serde_yaml::from_str("default: -5").unwrap();
+        let unknown = group("1", "Irgendwas", "Neuer Gruppentyp");
+        assert_eq!(priorities.resolve(&unknown), (-5, PrioritySource::Default));
+        // the shipped entries are not added back to a file that has none
+        assert_eq!(priorities.group_names.get("Ehemalige"), None);
+    }
+}
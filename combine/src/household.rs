@@ -0,0 +1,531 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::fs::{read_to_string, OpenOptions};
+use std::io::Write;
+
+// People who live together get a single couvert. Whether two people live together is decided by
+// a similarity score between 0 and 1:
+//
+// * the address counts most. Zip code, house number, postbox and country must be the same, and
+//   the streets are compared letter by letter, so that "Hauptstrasse" and "Hauptstr." or a typo
+//   still match.
+// * the last names count as well, "Müller" and "Mueller" count as the same name. People with
+//   different last names at the same address end up below the default threshold, but above the
+//   borderline threshold: they may be a patchwork family, or just neighbours in the same building.
+//   They are listed in the report, and households.yaml can put them `together`.
+//
+// All pairs whose score is above `threshold` are put into the same household, also transitively.
+// Pairs that are close, but not close enough, are listed in the report so that the user can check
+// them.
+//
+// When the heuristic is wrong, households.yaml can pin people together or keep them apart by their
+// hitobito id. `together` always wins, `apart` only prevents merges that the score would do.
+
+pub const HOUSEHOLDS_YAML_FILE: &str = "households.yaml";
+const HOUSEHOLDS_YAML_FILE_TEMPLATE: &str = r###"---
+# Personen mit einer Ähnlichkeit von mindestens `threshold` erhalten ein gemeinsames Couvert (0 bis 1).
+threshold: 0.8
+# Paare ab `borderline_threshold`, die nicht zusammengelegt wurden, werden nach dem Durchlauf aufgelistet.
+borderline_threshold: 0.6
+
+# Personen, die immer ein gemeinsames Couvert erhalten. Eine Liste von hitobito ids pro Couvert,
+# die ids stehen im Report und in der Datenbank im Link zur Person.
+together: []
+# z.B.:
+# together:
+#   - [1234, 5678]
+#   - [4321, 8765, 2109]
+
+# Personen, die nie ein gemeinsames Couvert erhalten, auch wenn sie am selben Ort wohnen.
+apart: []
+# z.B.:
+# apart:
+#   - [1234, 9012]
+
+# Diese Datei wird neu erstellt, wenn sie gelöscht wird.
+"###;
+
+/// the content of households.yaml
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct HouseholdSettings {
+    /// two people with at least this score share a couvert
+    #[serde(default = "default_threshold")]
+    pub threshold: f64,
+    /// pairs with at least this score, but below `threshold`, are shown in the report
+    #[serde(default = "default_borderline_threshold")]
+    pub borderline_threshold: f64,
+    /// hitobito ids of people that always share a couvert, one list per couvert
+    #[serde(default)]
+    pub together: Vec<Vec<usize>>,
+    /// hitobito ids of people that never share a couvert
+    #[serde(default)]
+    pub apart: Vec<Vec<usize>>,
+}
+
+fn default_threshold() -> f64 {
+    0.8
+}
+
+fn default_borderline_threshold() -> f64 {
+    0.6
+}
+
+impl Default for HouseholdSettings {
+    fn default() -> Self {
+        HouseholdSettings {
+            threshold: default_threshold(),
+            borderline_threshold: default_borderline_threshold(),
+            together: Vec::new(),
+            apart: Vec::new(),
+        }
+    }
+}
+
+/// what came out of `cluster`. All people are indices into the people that were clustered.
+#[derive(Debug)]
+pub struct Clustering {
+    /// the households, each a list of indices into the clustered people, in the order of their
+    /// first person
+    pub households: Vec<Vec<usize>>,
+    pub borderline_pairs: Vec<BorderlinePair>,
+    /// the entries of households.yaml that made a difference
+    pub overrides: Vec<AppliedOverride>,
+    /// ids in households.yaml that are not among the people
+    pub missing_ids: Vec<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AppliedOverride {
+    /// these people were put together because of `together`
+    Together(Vec<usize>),
+    /// these two would have been put together, but `apart` prevented it
+    Apart(usize, usize),
+    /// these two are listed in `apart`, but `together` put them into the same household
+    Conflict(usize, usize),
+}
+
+/// two people that almost, but not quite, were put into the same household
+#[derive(Debug, Clone)]
+pub struct BorderlinePair {
+    /// indices into the people that were clustered
+    pub first: usize,
+    pub second: usize,
+    pub score: f64,
+}
+
+/// Loads households.yaml. If it does not exist yet, a template is created and the defaults are
+/// used.
+///
+/// If households.yaml cannot be parsed, it is left untouched and the defaults are used.
+pub fn load_household_settings(user_interface: &dyn ui::UserInteractor) -> HouseholdSettings {
+    match read_to_string(HOUSEHOLDS_YAML_FILE) {
+        Ok(yaml) => match serde_yaml::from_str::<HouseholdSettings>(&yaml) {
+            Ok(settings) => settings,
+            Err(e) => {
+                user_interface.error_loading_households(&e);
+                HouseholdSettings::default()
+            },
+        },
+        Err(_e) => {
+            println!("combine: no {} yet. Creating it...", HOUSEHOLDS_YAML_FILE);
+            if let Err(e) = create_households_yaml_file_template() {
+                user_interface.error_loading_households(&e);
+            }
+            HouseholdSettings::default()
+        },
+    }
+}
+
+pub fn create_households_yaml_file_template() -> Result<(), std::io::Error> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(HOUSEHOLDS_YAML_FILE)?;
+    file.write_all(HOUSEHOLDS_YAML_FILE_TEMPLATE.as_bytes())?;
+    Ok(())
+}
+
+/// Groups `people` into households, which have to be sorted by zip code.
+pub fn cluster(
+    people: &Vec<dbparse::ReasonablePerson>,
+    settings: &HouseholdSettings,
+) -> Clustering {
+    let index_of: HashMap<usize, usize> = people
+        .iter()
+        .enumerate()
+        .map(|(index, person)| (person.id, index))
+        .collect();
+    let mut missing_ids: BTreeSet<usize> = BTreeSet::new();
+    let mut indices_of = |ids: &Vec<usize>| -> Vec<usize> {
+        ids.iter()
+            .filter_map(|id| {
+                let index = index_of.get(id).cloned();
+                if index.is_none() {
+                    missing_ids.insert(*id);
+                }
+                index
+            })
+            .collect()
+    };
+
+    let mut households = UnionFind::new(people.len());
+    let mut overrides: Vec<AppliedOverride> = Vec::new();
+
+    for ids in settings.together.iter() {
+        let indices = indices_of(ids);
+        for pair in indices.windows(2) {
+            households.union(pair[0], pair[1]);
+        }
+        if indices.len() > 1 {
+            overrides.push(AppliedOverride::Together(indices));
+        }
+    }
+
+    let mut apart_pairs: Vec<(usize, usize)> = Vec::new();
+    for ids in settings.apart.iter() {
+        let indices = indices_of(ids);
+        for (position, &first) in indices.iter().enumerate() {
+            for &second in indices.iter().skip(position + 1) {
+                apart_pairs.push((first, second));
+            }
+        }
+    }
+    for &(first, second) in apart_pairs.iter() {
+        if households.find(first) == households.find(second) {
+            overrides.push(AppliedOverride::Conflict(first, second));
+        }
+    }
+
+    // the most similar pairs first, so that `apart` blocks the weaker links
+    let mut candidates: Vec<BorderlinePair> = Vec::new();
+    for first in 0..people.len() {
+        for second in (first + 1)..people.len() {
+            // people are sorted by zip code, and different zip codes never match
+            if people[first].address.zip_code != people[second].address.zip_code {
+                break;
+            }
+            let score = similarity(&people[first], &people[second]);
+            if score >= settings.borderline_threshold {
+                candidates.push(BorderlinePair {
+                    first: first,
+                    second: second,
+                    score: score,
+                });
+            }
+        }
+    }
+    candidates.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+
+    let mut borderline_pairs: Vec<BorderlinePair> = Vec::new();
+    for candidate in candidates.into_iter() {
+        let (first, second) = (candidate.first, candidate.second);
+        if households.find(first) == households.find(second) {
+            continue;
+        }
+        if households.kept_apart(first, second, &apart_pairs) {
+            if candidate.score >= settings.threshold {
+                overrides.push(AppliedOverride::Apart(first, second));
+            }
+        } else if candidate.score >= settings.threshold {
+            households.union(first, second);
+        } else {
+            borderline_pairs.push(candidate);
+        }
+    }
+
+    // pairs that ended up together anyway through somebody else are not borderline anymore
+    borderline_pairs.retain(|pair| households.find(pair.first) != households.find(pair.second));
+    borderline_pairs.sort_by_key(|pair| (pair.first, pair.second));
+
+    Clustering {
+        households: households.groups(),
+        borderline_pairs: borderline_pairs,
+        overrides: overrides,
+        missing_ids: missing_ids.into_iter().collect(),
+    }
+}
+
+/// how likely it is that `a` and `b` live in the same household, between 0 and 1
+pub fn similarity(a: &dbparse::ReasonablePerson, b: &dbparse::ReasonablePerson) -> f64 {
+    let address = address_similarity(&a.address, &b.address);
+    let name = name_similarity(&a.last_name, &b.last_name);
+    return address * (0.6 + 0.4 * name);
+}
+
+fn address_similarity(a: &dbparse::PostalAddress, b: &dbparse::PostalAddress) -> f64 {
+    let differ = |x: &str, y: &str| fold(x) != fold(y);
+    let both_set_and_differ = |x: &str, y: &str| !x.is_empty() && !y.is_empty() && differ(x, y);
+    if differ(&a.zip_code, &b.zip_code)
+        || differ(&a.house_number, &b.house_number)
+        || differ(&a.postbox, &b.postbox)
+        || both_set_and_differ(&a.addition, &b.addition)
+        || crate::country::countries_differ(a, b)
+    {
+        return 0.0;
+    }
+    if a.same_household(b) {
+        return 1.0;
+    }
+    return string_similarity(&fold(&a.street_key()), &fold(&b.street_key()));
+}
+
+fn name_similarity(a: &str, b: &str) -> f64 {
+    let (a, b) = (fold(a), fold(b));
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    // double names, e.g. "Muster-Beispiel" and "Beispiel"
+    if a != b && (a.contains(&b) || b.contains(&a)) {
+        return 0.9;
+    }
+    return string_similarity(&a, &b);
+}
+
+/// lower case, umlauts written out, only letters and digits. "Müller" and "Mueller" both become
+/// "mueller".
+fn fold(s: &str) -> String {
+    let mut folded = String::with_capacity(s.len());
+    for c in s.to_lowercase().chars() {
+        match c {
+            'ä' => folded.push_str("ae"),
+            'ö' => folded.push_str("oe"),
+            'ü' => folded.push_str("ue"),
+            'ß' => folded.push_str("ss"),
+            'à' | 'á' | 'â' => folded.push('a'),
+            'è' | 'é' | 'ê' | 'ë' => folded.push('e'),
+            'ì' | 'í' | 'î' | 'ï' => folded.push('i'),
+            'ò' | 'ó' | 'ô' => folded.push('o'),
+            'ù' | 'ú' | 'û' => folded.push('u'),
+            'ç' => folded.push('c'),
+            c if c.is_alphanumeric() => folded.push(c),
+            _ => (),
+        }
+    }
+    return folded;
+}
+
+/// 1 minus the edit distance relative to the longer string
+fn string_similarity(a: &str, b: &str) -> f64 {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let longer = std::cmp::max(a.len(), b.len());
+    if longer == 0 {
+        return 1.0;
+    }
+    // levenshtein distance, one row at a time
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        let mut current = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let substitution = previous[j - 1] + if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            current[j] = substitution.min(previous[j] + 1).min(current[j - 1] + 1);
+        }
+        previous = current;
+    }
+    return 1.0 - previous[b.len()] as f64 / longer as f64;
+}
+
+struct UnionFind {
+    parent: Vec<usize>,
+}
+
+impl UnionFind {
+    fn new(size: usize) -> Self {
+        UnionFind {
+            parent: (0..size).collect(),
+        }
+    }
+
+    fn find(&mut self, x: usize) -> usize {
+        let mut root = x;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+        // shorten the path for the next time
+        let mut current = x;
+        while self.parent[current] != root {
+            let next = self.parent[current];
+            self.parent[current] = root;
+            current = next;
+        }
+        root
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (root_a, root_b) = (self.find(a), self.find(b));
+        // the smaller index stays the root, so that households keep the order of the people
+        if root_a < root_b {
+            self.parent[root_b] = root_a;
+        } else {
+            self.parent[root_a] = root_b;
+        }
+    }
+
+    /// whether the sets of `a` and `b` contain two people that must stay apart
+    fn kept_apart(&mut self, a: usize, b: usize, apart_pairs: &Vec<(usize, usize)>) -> bool {
+        let (root_a, root_b) = (self.find(a), self.find(b));
+        for &(x, y) in apart_pairs.iter() {
+            let (root_x, root_y) = (self.find(x), self.find(y));
+            if (root_x == root_a && root_y == root_b) || (root_x == root_b && root_y == root_a) {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// all sets, ordered by their smallest element
+    fn groups(&mut self) -> Vec<Vec<usize>> {
+        let mut groups: Vec<Vec<usize>> = Vec::new();
+        let mut group_of_root: HashMap<usize, usize> = HashMap::new();
+        for x in 0..self.parent.len() {
+            let root = self.find(x);
+            let next_group = groups.len();
+            let group = *group_of_root.entry(root).or_insert(next_group);
+            if group == groups.len() {
+                groups.push(Vec::new());
+            }
+            groups[group].push(x);
+        }
+        groups
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    fn person(last_name: &str, street_line: &str) -> dbparse::ReasonablePerson {
+        person_with_id(1, last_name, street_line)
+    }
+
+    fn person_with_id(id: usize, last_name: &str, street_line: &str) -> dbparse::ReasonablePerson {
+        dbparse::ReasonablePerson {
+            id: id,
+            first_name: String::from("Anna"),
+            last_name: String::from(last_name),
+            address: dbparse::PostalAddress::parse(street_line, "8330", "Pfäffikon ZH"),
+            ..dbparse::ReasonablePerson::default()
+        }
+    }
+
+    #[test]
+    fn test_fold() {
+        assert_eq!(super::fold("Müller"), super::fold("Mueller"));
+        assert_eq!(super::fold("Hauptstr. 5"), "hauptstr5");
+    }
+
+    #[test]
+    fn test_similarity() {
+        let settings = super::HouseholdSettings::default();
+        let same = super::similarity(
+            &person("Müller", "Hauptstrasse 5"),
+            &person("Mueller", "Hauptstr. 5a"),
+        );
+        assert_eq!(same, 1.0);
+        // patchwork family or neighbours in the same building, for the user to decide
+        let same_building = super::similarity(
+            &person("Muster", "Hauptstrasse 5"),
+            &person("Beispiel", "Hauptstrasse 5"),
+        );
+        assert!(same_building < settings.threshold, "{}", same_building);
+        assert!(
+            same_building >= settings.borderline_threshold,
+            "{}",
+            same_building
+        );
+        let double_name = super::similarity(
+            &person("Muster", "Hauptstrasse 5"),
+            &person("Muster-Beispiel", "Hauptstrasse 5"),
+        );
+        assert!(double_name >= settings.threshold, "{}", double_name);
+        let typo = super::similarity(
+            &person("Muster", "Hauptstrasse 5"),
+            &person("Beispiel", "Hauptstrase 5"),
+        );
+        assert!(typo < settings.threshold, "{}", typo);
+        assert!(typo >= settings.borderline_threshold, "{}", typo);
+        let neighbours = super::similarity(
+            &person("Muster", "Hauptstrasse 5"),
+            &person("Muster", "Hauptstrasse 7"),
+        );
+        assert_eq!(neighbours, 0.0);
+
+        let country = |last_name: &str, country: &str| {
+            let mut person = person(last_name, "Hauptstrasse 5");
+            person.address.country = Some(String::from(country));
+            person
+        };
+        let aliases = super::similarity(&country("Muster", "CH"), &country("Muster", "Schweiz"));
+        assert_eq!(aliases, 1.0);
+        let abroad = super::similarity(&country("Muster", "CH"), &country("Muster", "DE"));
+        assert_eq!(abroad, 0.0);
+    }
+
+    #[test]
+    fn test_cluster_is_transitive() {
+        let people = vec![
+            person("Muster", "Hauptstrasse 5"),
+            person("Muster", "Dorfstrasse 1"),
+            person("Mustér", "Hauptstr. 5"),
+            person("Beispiel", "Hauptstrase 5"),
+        ];
+        let clustering = super::cluster(&people, &super::HouseholdSettings::default());
+        assert_eq!(clustering.households, vec![vec![0, 2], vec![1], vec![3]]);
+        assert_eq!(clustering.borderline_pairs.len(), 2);
+        assert!(clustering
+            .borderline_pairs
+            .iter()
+            .all(|pair| pair.second == 3));
+        assert!(clustering.overrides.is_empty());
+    }
+
+    #[test]
+    fn test_overrides() {
+        let people = vec![
+            person_with_id(10, "Muster", "Hauptstrasse 5"),
+            person_with_id(11, "Muster", "Hauptstrasse 5"),
+            person_with_id(12, "Beispiel", "Dorfstrasse 1"),
+            person_with_id(13, "Muster", "Hauptstrasse 5"),
+        ];
+        let settings: super::HouseholdSettings = serde_yaml::from_str(
+            r###"---
+together:
+  - [11, 12, 99]
+apart:
+  - [10, 11]
+"###,
+        )
+        .unwrap();
+        let clustering = super::cluster(&people, &settings);
+
+        // 13 can join either 10 or 11, but not both
+        assert_eq!(clustering.households.len(), 2);
+        assert!(clustering
+            .households
+            .iter()
+            .any(|household| household.contains(&1) && household.contains(&2)));
+        assert!(clustering
+            .households
+            .iter()
+            .all(|household| !(household.contains(&0) && household.contains(&1))));
+        assert_eq!(clustering.missing_ids, vec![99]);
+        assert_eq!(
+            clustering.overrides[0],
+            super::AppliedOverride::Together(vec![1, 2])
+        );
+        assert!(clustering
+            .overrides
+            .iter()
+            .any(|o| *o == super::AppliedOverride::Apart(0, 1)
+                || *o == super::AppliedOverride::Apart(0, 3)));
+    }
+
+    #[test]
+    fn test_template_is_valid() {
+        let settings: super::HouseholdSettings =
+            serde_yaml::from_str(super::HOUSEHOLDS_YAML_FILE_TEMPLATE).unwrap();
+        assert_eq!(settings, super::HouseholdSettings::default());
+    }
+}
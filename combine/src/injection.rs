@@ -1,7 +1,23 @@
 use pdfgen::CouvertInfo;
+use serde::{Deserialize, Deserializer};
+use std::collections::{HashMap, HashSet};
 use std::fs::OpenOptions;
 use std::io::{Read, Write};
 
+// inject_people.yaml lists receivers that are not in the database. An entry is either a finished
+// couvert (with `receivers` and `address`), which is printed as it is, or a person, which is
+// treated like a person from the database: its address is normalized, it gets its badge from
+// roles.yaml and its group from mapping.yaml, and it shares a couvert with the people it lives
+// with.
+//
+// Every entry is checked on its own, so that a typo in one entry does not lose all the others.
+// The errors say in which line the entry starts.
+//
+// Injected people need ids that no person from the database has, otherwise an entry in
+// households.yaml or exclude.yaml would hit both. hitobito ids are far below `INJECTED_ID_START`,
+// so the ids the user chooses have to be in the range from there to `AUTOMATIC_ID_START`. People
+// without an id get one above that, counted by their entry.
+
 pub const INJECTION_YAML_FILE_PATH: &str = "inject_people.yaml";
 const INJECTION_YAML_FILE_TEMPLATE: &str = r###"---
 # remove the following line (or comment it out):
@@ -15,6 +31,8 @@ const INJECTION_YAML_FILE_TEMPLATE: &str = r###"---
 #     - Herbert Herber
 #     - Herbertstrasse h32
 #     - 8332 Herbhausen
+#   # optional, only for couverts that leave Switzerland:
+#   country: DEUTSCHLAND
 # - receivers:
 #     - nickname: Zweibert
 #       group: Herbert Fan Club
@@ -31,11 +49,119 @@ const INJECTION_YAML_FILE_TEMPLATE: &str = r###"---
 #     - Herbert Herber
 #     - Herbertstrasse h32
 #     - 8332 Herbhausen
+#
+# Instead of a whole couvert, an entry can also be a person. People are treated like the people
+# from the database, so a guest who lives with a member ends up in the member's couvert:
+# - first_name: Gerda
+#   last_name: Gast
+#   nickname: Gästli        # optional
+#   address: Hauptstrasse 5
+#   zip_code: 8330
+#   town: Pfäffikon ZH
+#   country: DE             # optional
+#   group_id: 1341          # optional, the id of the group in mapping.yaml
+#   role: Teilnehmer/-in    # optional, a role from roles.yaml
+#   id: 900000001           # optional, to refer to the person in households.yaml or exclude.yaml.
+#                           # Between 900000000 and 989999999, so that it is not a database id.
 
 # Each receiver will get their own envelope. But the envelopes will be sorted like all the other envelopes, by group name.
 # This File will be regenerated if you delete it
 "###;
 
+/// the smallest id the user may give an injected person
+const INJECTED_ID_START: usize = 900_000_000;
+/// injected people without an id get `AUTOMATIC_ID_START` plus the number of their entry
+const AUTOMATIC_ID_START: usize = 990_000_000;
+
+/// a person from inject_people.yaml, before it is turned into a `dbparse::ReasonablePerson`
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct InjectedPerson {
+    /// only needed to refer to the person in households.yaml or exclude.yaml
+    #[serde(default)]
+    id: Option<usize>,
+    #[serde(default)]
+    first_name: String,
+    #[serde(default)]
+    last_name: String,
+    #[serde(default)]
+    nickname: String,
+    /// the street line, or several lines like the address field of the database
+    #[serde(default)]
+    address: String,
+    #[serde(deserialize_with = "string_or_number")]
+    zip_code: String,
+    town: String,
+    #[serde(default)]
+    country: String,
+    #[serde(default)]
+    salutation_parents: String,
+    #[serde(default)]
+    name_parents: String,
+    /// "YYYY-MM-DD"
+    #[serde(default)]
+    birthday: String,
+    #[serde(default)]
+    group_id: Option<usize>,
+    /// a role_type like in roles.yaml
+    #[serde(default)]
+    role: Option<String>,
+}
+
+/// everything that inject_people.yaml adds
+#[derive(Default)]
+pub struct Injections {
+    /// go through the same steps as the people from the database
+    pub people: Vec<dbparse::ReasonablePerson>,
+    /// are printed as they are
+    pub couverts: Vec<CouvertInfo>,
+}
+
+/// a problem with one entry of inject_people.yaml, or with the file as a whole
+#[derive(Debug)]
+pub struct InjectionError {
+    /// counted from 1
+    pub entry: Option<usize>,
+    /// counted from 1, the line where the entry starts
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl std::fmt::Display for InjectionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.entry, self.line) {
+            (Some(entry), Some(line)) => write!(
+                f,
+                "{}, Eintrag {} (Zeile {}): {}",
+                INJECTION_YAML_FILE_PATH, entry, line, self.message
+            ),
+            (Some(entry), None) => write!(
+                f,
+                "{}, Eintrag {}: {}",
+                INJECTION_YAML_FILE_PATH, entry, self.message
+            ),
+            _ => write!(f, "{}: {}", INJECTION_YAML_FILE_PATH, self.message),
+        }
+    }
+}
+
+impl std::error::Error for InjectionError {}
+
+/// zip codes are numbers in most countries, but not in all
+fn string_or_number<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match serde_yaml::Value::deserialize(deserializer)? {
+        serde_yaml::Value::String(s) => Ok(s),
+        serde_yaml::Value::Number(n) => Ok(n.to_string()),
+        other => Err(serde::de::Error::custom(format!(
+            "expected a string or a number, found {:?}",
+            other
+        ))),
+    }
+}
+
 // mostly for debug purposes
 #[allow(dead_code)]
 fn serialize_couvert_infos(yaml_text: &str) {
@@ -61,11 +187,27 @@ pub fn create_injection_yaml_file_empty() -> Result<std::fs::File, std::io::Erro
         .open(INJECTION_YAML_FILE_PATH)
 }
 
-/// Reads from `inject_people.yaml` and adds those persons to the parameter `couvert_infos`
-pub fn inject_couvert_infos(
-    mut couvert_infos: &mut Vec<CouvertInfo>,
+/// Reads `inject_people.yaml`. `groups` are the groups from the database, injected people can only
+/// be in one of them. Entries with errors are reported to the user and left out.
+pub fn load_injections(
+    groups: &HashSet<dbparse::ReasonableGroup>,
     user_interface: &dyn ui::UserInteractor,
-) {
+) -> Injections {
+    match read_injection_file(user_interface) {
+        Some(text) => {
+            let (injections, errors) = parse_injections(&text, groups);
+            for error in errors.iter() {
+                println!("combine::inject: {}", error);
+                user_interface.error_injecting_couverts(error);
+            }
+            injections
+        },
+        None => Injections::default(),
+    }
+}
+
+/// the content of `inject_people.yaml`, which is created from the template if it does not exist
+fn read_injection_file(user_interface: &dyn ui::UserInteractor) -> Option<String> {
     // create empty-ish template file iff there is no current file there
     let fi = create_injection_yaml_file_empty();
 
@@ -85,7 +227,7 @@ pub fn inject_couvert_infos(
                     INJECTION_YAML_FILE_PATH
                 );
                 user_interface.error_injecting_couverts(&e);
-                return;
+                return None;
             },
             Ok(mut file) => {
                 let mut text = String::new();
@@ -96,10 +238,9 @@ pub fn inject_couvert_infos(
                             INJECTION_YAML_FILE_PATH
                         );
                         user_interface.error_injecting_couverts(&error);
+                        return None;
                     },
-                    Ok(_success_code) => {
-                        parse_and_append(&text, couvert_infos, user_interface);
-                    },
+                    Ok(_success_code) => return Some(text),
                 }
             },
         }
@@ -113,27 +254,291 @@ pub fn inject_couvert_infos(
         }
 
         // and use template string as text
-        parse_and_append(
-            &INJECTION_YAML_FILE_TEMPLATE,
-            &mut couvert_infos,
-            user_interface,
-        );
+        return Some(INJECTION_YAML_FILE_TEMPLATE.to_string());
     };
 }
 
-fn parse_and_append(
+/// the line of each entry of the top level list, counted from 1. `None` if the list is not
+/// written as one "- " per entry.
+fn entry_lines(text: &str, entry_count: usize) -> Vec<Option<usize>> {
+    let lines: Vec<usize> = text
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| *line == "-" || line.starts_with("- "))
+        .map(|(i, _)| i + 1)
+        .collect();
+    if lines.len() == entry_count {
+        lines.into_iter().map(Some).collect()
+    } else {
+        vec![None; entry_count]
+    }
+}
+
+fn parse_injections(
     text: &str,
-    couvert_infos: &mut Vec<CouvertInfo>,
-    user_interface: &dyn ui::UserInteractor,
-) {
-    let content_result: Result<Vec<CouvertInfo>, serde_yaml::Error> = serde_yaml::from_str(text);
-    match content_result {
-        Ok(mut content) => {
-            couvert_infos.append(&mut content);
-        },
+    groups: &HashSet<dbparse::ReasonableGroup>,
+) -> (Injections, Vec<InjectionError>) {
+    let mut injections = Injections::default();
+    let mut errors: Vec<InjectionError> = Vec::new();
+
+    let entries: Vec<serde_yaml::Value> = match serde_yaml::from_str(text) {
+        Ok(entries) => entries,
         Err(e) => {
-            println!("Parsing failed: {:?}", e);
-            user_interface.error_injecting_couverts(&e);
+            errors.push(InjectionError {
+                entry: None,
+                line: e.location().map(|location| location.line()),
+                message: e.to_string(),
+            });
+            return (injections, errors);
+        },
+    };
+    let lines = entry_lines(text, entries.len());
+    // the entry and line of every id that has been used
+    let mut used_ids: HashMap<usize, (usize, Option<usize>)> = HashMap::new();
+
+    for (i, entry) in entries.into_iter().enumerate() {
+        let error = |message: String| InjectionError {
+            entry: Some(i + 1),
+            line: lines[i],
+            message: message,
+        };
+        let is_couvert = entry
+            .as_mapping()
+            .map(|mapping| mapping.contains_key(&serde_yaml::Value::from("receivers")))
+            .unwrap_or(false);
+        if is_couvert {
+            match serde_yaml::from_value::<CouvertInfo>(entry) {
+                Ok(couvert) if couvert.receivers.is_empty() => {
+                    errors.push(error(String::from("receivers ist leer")))
+                },
+                Ok(couvert) => injections.couverts.push(couvert),
+                Err(e) => errors.push(error(e.to_string())),
+            }
+        } else {
+            match serde_yaml::from_value::<InjectedPerson>(entry) {
+                Ok(person) => match to_reasonable_person(person, i, groups) {
+                    Ok(person) => match used_ids.get(&person.id) {
+                        Some((entry, line)) => errors.push(error(format!(
+                            "die id {} hat schon Eintrag {}{}",
+                            person.id,
+                            entry,
+                            line.map(|line| format!(" (Zeile {})", line))
+                                .unwrap_or_default()
+                        ))),
+                        None => {
+                            used_ids.insert(person.id, (i + 1, lines[i]));
+                            injections.people.push(person);
+                        },
+                    },
+                    Err(message) => errors.push(error(message)),
+                },
+                Err(e) => errors.push(error(e.to_string())),
+            }
+        }
+    }
+    return (injections, errors);
+}
+
+/// `index` is the position of the entry in the file, to tell the roles apart
+fn to_reasonable_person(
+    injected: InjectedPerson,
+    index: usize,
+    groups: &HashSet<dbparse::ReasonableGroup>,
+) -> Result<dbparse::ReasonablePerson, String> {
+    if injected.first_name.trim().is_empty() && injected.last_name.trim().is_empty() {
+        return Err(String::from("first_name oder last_name fehlt"));
+    }
+    let id = match injected.id {
+        Some(id) if id < INJECTED_ID_START || id >= AUTOMATIC_ID_START => {
+            return Err(format!(
+                "die id {} muss zwischen {} und {} liegen, damit sie nicht mit einer id aus der Datenbank verwechselt wird",
+                id,
+                INJECTED_ID_START,
+                AUTOMATIC_ID_START - 1
+            ));
         },
+        Some(id) => id,
+        None => AUTOMATIC_ID_START + index + 1,
+    };
+    let address =
+        dbparse::PostalAddress::parse(&injected.address, &injected.zip_code, &injected.town)
+            .with_country(&injected.country);
+    if !address.is_complete() {
+        return Err(String::from(
+            "die Adresse ist unvollständig, es braucht address, zip_code und town",
+        ));
+    }
+
+    let mut person = dbparse::ReasonablePerson {
+        id: id,
+        first_name: injected.first_name.trim().to_string(),
+        last_name: injected.last_name.trim().to_string(),
+        nickname: injected.nickname.trim().to_string(),
+        address: address,
+        salutation_parents: injected.salutation_parents.trim().to_string(),
+        name_parents: injected.name_parents.trim().to_string(),
+        birthday: injected.birthday.trim().to_string(),
+        roles: HashSet::new(),
+        groups: HashSet::new(),
+    };
+
+    let group = match injected.group_id {
+        Some(group_id) => Some(
+            groups
+                .iter()
+                .find(|group| group.inner_group.id == group_id.to_string())
+                .ok_or(format!(
+                    "die Gruppe {} gibt es nicht in der Datenbank",
+                    group_id
+                ))?
+                .clone(),
+        ),
+        None => None,
     };
+    if let Some(role_type) = injected.role {
+        person.roles.insert(dbparse::Role::new(
+            std::rc::Rc::<str>::from(format!("injected-{}", index).as_str()),
+            role_type,
+            None,
+            group
+                .as_ref()
+                .map(|group| group.inner_group.id.clone())
+                .unwrap_or_default(),
+            String::new(),
+        ));
+    }
+    if let Some(group) = group {
+        person.groups.insert(group);
+    }
+    return Ok(person);
+}
+
+#[cfg(test)]
+mod tests {
+    fn groups() -> std::collections::HashSet<dbparse::ReasonableGroup> {
+        let mut groups = std::collections::HashSet::new();
+        groups.insert(dbparse::ReasonableGroup {
+            inner_group: dbparse::Group {
+                id: String::from("1341"),
+                name: String::from("Senshi (M)"),
+                group_type: String::from("Stufe"),
+            },
+        });
+        groups
+    }
+
+    #[test]
+    fn test_template_is_valid() {
+        let (injections, errors) =
+            super::parse_injections(super::INJECTION_YAML_FILE_TEMPLATE, &groups());
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert!(injections.people.is_empty());
+        assert!(injections.couverts.is_empty());
+    }
+
+    #[test]
+    fn test_couverts_and_people() {
+        let yaml = r###"---
+- receivers:
+    - nickname: Herbert
+      group: Herbert Fan Club
+      role: Teilnehmer
+  address:
+    - Herbert Herber
+    - 8332 Herbhausen
+- first_name: Gerda
+  last_name: Gast
+  address: Hauptstr. 5
+  zip_code: 8330
+  town: Pfäffikon ZH
+  group_id: 1341
+  role: Teilnehmer/-in
+"###;
+        let (injections, errors) = super::parse_injections(yaml, &groups());
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(injections.couverts.len(), 1);
+        assert_eq!(injections.people.len(), 1);
+        let gerda = &injections.people[0];
+        assert_eq!(gerda.address.zip_code, "8330");
+        assert_eq!(gerda.address.house_number, "5");
+        assert_eq!(gerda.groups.len(), 1);
+        let role = gerda.roles.iter().next().expect("should have a role");
+        assert_eq!(role.role_type, "Teilnehmer/-in");
+        assert_eq!(role.group_id(), "1341");
+    }
+
+    #[test]
+    fn test_errors_name_the_line() {
+        let yaml = r###"---
+- first_name: Gerda
+  last_name: Gast
+  address: Hauptstrasse 5
+  zip_code: "8330"
+  town: Pfäffikon ZH
+- first_name: Tippfehler
+  adress: Hauptstrasse 5
+  zip_code: "8330"
+  town: Pfäffikon ZH
+- first_name: Niemand
+  address: Hauptstrasse 5
+  zip_code: "8330"
+  town: Pfäffikon ZH
+  group_id: 99
+"###;
+        let (injections, errors) = super::parse_injections(yaml, &groups());
+        assert_eq!(injections.people.len(), 1);
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].line, Some(7));
+        assert!(errors[0].message.contains("adress"), "{}", errors[0]);
+        assert_eq!(errors[1].line, Some(11));
+        assert!(errors[1].to_string().contains("Zeile 11"));
+    }
+
+    #[test]
+    fn test_ids_do_not_collide() {
+        let yaml = r###"---
+- first_name: Gerda
+  address: Hauptstrasse 5
+  zip_code: "8330"
+  town: Pfäffikon ZH
+- first_name: Hans
+  address: Hauptstrasse 5
+  zip_code: "8330"
+  town: Pfäffikon ZH
+- first_name: Gast
+  address: Hauptstrasse 5
+  zip_code: "8330"
+  town: Pfäffikon ZH
+  id: 900000001
+- first_name: Doppelt
+  address: Hauptstrasse 5
+  zip_code: "8330"
+  town: Pfäffikon ZH
+  id: 900000001
+- first_name: Datenbank
+  address: Hauptstrasse 5
+  zip_code: "8330"
+  town: Pfäffikon ZH
+  id: 6468
+"###;
+        let (injections, errors) = super::parse_injections(yaml, &groups());
+        let ids: Vec<usize> = injections.people.iter().map(|p| p.id).collect();
+        assert_eq!(ids, vec![990000001, 990000002, 900000001]);
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].line, Some(15));
+        assert!(
+            errors[0].message.contains("Eintrag 3 (Zeile 10)"),
+            "{}",
+            errors[0]
+        );
+        assert_eq!(errors[1].line, Some(20));
+        assert!(errors[1].message.contains("6468"), "{}", errors[1]);
+    }
+
+    #[test]
+    fn test_syntax_error_has_a_line() {
+        let (_injections, errors) = super::parse_injections("---\n- a: [\n", &groups());
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].line.is_some());
+    }
 }
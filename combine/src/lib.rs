@@ -1,8 +1,17 @@
 use dbparse;
 use pdfgen;
 use regex;
+mod addressee;
+mod country;
+mod exclusion;
+mod grouppriority;
+mod household;
 mod injection;
+mod report;
 mod roletranslation;
+mod sorting;
+pub use pdfgen::{EnvelopeFormat, GroupSeparator, LabelFormat, LetterTemplate, WindowPosition};
+pub use sorting::SortOrder;
 
 /// all files that the user might modify to set config
 /// used in --info and in --clean
@@ -14,44 +23,162 @@ pub fn get_USER_RELEVANT_FILES() -> Vec<&'static dyn AsRef<std::path::Path>> {
         &dbparse::MAPPING_YAML_FILE,
         &dbparse::CONFIG_YAML_FILE,
         &crate::injection::INJECTION_YAML_FILE_PATH,
+        &crate::roletranslation::ROLES_YAML_FILE,
+        &crate::grouppriority::GROUP_PRIORITIES_YAML_FILE,
+        &crate::household::HOUSEHOLDS_YAML_FILE,
+        &crate::exclusion::EXCLUDE_YAML_FILE,
+        &pdfgen::layout::LAYOUT_YAML_FILE,
     ]
 }
 
 pub fn main_cli_ui() {
     let user_interface = ui::CliUi {};
     let params = PrintingParameters::new().print_sidebadges(true);
-    main(&user_interface, &params);
+    if let Err(e) = main(&user_interface, &params) {
+        std::process::exit(e.exit_code());
+    }
 }
 
+/// Errors have already been shown to the user through `user_interface` when this returns
 #[cfg(not(target_arch = "wasm32"))]
-pub fn main(user_interface: &dyn ui::UserInteractor, printing_parameters: &PrintingParameters) {
+pub fn main(
+    user_interface: &dyn ui::UserInteractor,
+    printing_parameters: &PrintingParameters,
+) -> Result<(), dbparse::Error> {
     let dbparse_interactor = DbparseRedirector {
         user_interface: Some(user_interface),
     };
 
-    println!("combine: loading data from database");
-    let database_returns: Result<dbparse::MainReturns, Box<dyn std::error::Error>> =
-        dbparse::run(&dbparse_interactor);
-    if database_returns.is_err() {
-        std::process::exit(1);
-    }
-    let ret_db: dbparse::MainReturns = database_returns.unwrap();
+    let database_returns: Result<dbparse::MainReturns, dbparse::Error> =
+        match &printing_parameters.snapshot {
+            None => {
+                println!("combine: loading data from database");
+                dbparse::run(&dbparse_interactor, printing_parameters.telemetry)
+            },
+            Some(snapshot_path) => {
+                println!("combine: loading data from snapshot {}", snapshot_path);
+                dbparse::run_from_snapshot(&dbparse_interactor, snapshot_path)
+            },
+        };
+    let ret_db: dbparse::MainReturns = database_returns?;
     let mapping: dbparse::mapping::GroupMapping = ret_db.group_mapping;
     let mut dataset: dbparse::ReasonableDataset = ret_db.dataset;
     user_interface.on_parsing_finished();
+    let injections = injection::load_injections(dataset.get_groups(), user_interface);
+    dataset.people.extend(injections.people);
+    let settings = UserSettings {
+        role_translation: roletranslation::load_role_translation(&dataset.people, user_interface),
+        group_priorities: grouppriority::load_group_priorities(
+            dataset.people.iter().flat_map(|person| person.groups.iter()),
+            user_interface,
+        ),
+        households: household::load_household_settings(user_interface),
+    };
 
-    let mut couvert_infos: Vec<pdfgen::CouvertInfo> =
-        merge_households(&mut dataset.people, &mapping, user_interface, !printing_parameters.merge_flatmates);
-    injection::inject_couvert_infos(&mut couvert_infos, user_interface);
-    couvert_infos.sort_by(|a: &pdfgen::CouvertInfo, b: &pdfgen::CouvertInfo| {
-        a.receivers[0].group.cmp(&b.receivers[0].group)
-    });
+    let mut run_report = report::RunReport::new();
+    let exclusions = exclusion::apply(
+        &mut dataset.people,
+        &exclusion::load_exclusion_list(user_interface),
+    );
+    run_report.add_section(exclusions_section(&exclusions));
+    let mut couvert_infos: Vec<pdfgen::CouvertInfo> = merge_households(
+        &mut dataset.people,
+        &mapping,
+        &settings,
+        user_interface,
+        &mut run_report,
+        !printing_parameters.merge_flatmates,
+    );
+    couvert_infos.extend(injections.couverts);
+    printing_parameters.sort_order.sort(&mut couvert_infos);
+    if couvert_infos.is_empty() {
+        user_interface.inform_user("Nobody is left to send a couvert to, see exclude.yaml.");
+    }
+
+    if printing_parameters.dry_run {
+        run_report.add_section(group_choice_section(
+            &dataset.people,
+            &mapping,
+            &settings.group_priorities,
+        ));
+    }
+    let layout = pdfgen::layout::load_layout(user_interface);
+    let fonts = pdfgen::Fonts::load(&layout.fonts, user_interface);
+    run_report.add_section(glyphs_section(fonts.preflight(&couvert_infos, &layout)));
+
+    let rendered_report = run_report.render();
+    if !rendered_report.is_empty() {
+        user_interface.show_report(&rendered_report);
+    }
+    if printing_parameters.dry_run {
+        println!("combine: dry run, not creating a pdf");
+        return Ok(());
+    }
+
+    if let Some(label_format) = printing_parameters.label_format {
+        let sheet = load_label_sheet(label_format, user_interface)?;
+        let start_offset = if printing_parameters.label_offset < sheet.labels_per_sheet() {
+            printing_parameters.label_offset
+        } else {
+            user_interface.inform_user(&format!(
+                "A sheet only has {} labels, ignoring the label offset {}.",
+                sheet.labels_per_sheet(),
+                printing_parameters.label_offset
+            ));
+            0
+        };
+
+        println!("combine: creating label pdf");
+        let doc_generated = pdfgen::generate_labels(
+            &couvert_infos,
+            Some(user_interface),
+            &sheet,
+            start_offset,
+            &fonts,
+        );
+        let mut outfile = std::io::BufWriter::new(
+            std::fs::File::create("output_etiketten.pdf").expect("Failed to create file..."),
+        );
+        doc_generated
+            .save(&mut outfile)
+            .expect("Failed to save file...");
+        return Ok(());
+    }
+
+    if let Some(window) = printing_parameters.letter_window {
+        println!("combine: creating letter pdf");
+        let doc_generated = pdfgen::generate_letters(
+            &couvert_infos,
+            Some(user_interface),
+            window,
+            printing_parameters.letter_template.as_ref(),
+            &layout,
+            &fonts,
+        );
+        let mut outfile = std::io::BufWriter::new(
+            std::fs::File::create("output_briefe.pdf").expect("Failed to create file..."),
+        );
+        doc_generated
+            .save(&mut outfile)
+            .expect("Failed to save file...");
+        return Ok(());
+    }
+
+    let envelope_format = match printing_parameters.envelope_format {
+        Some(envelope_format) => envelope_format,
+        None => load_envelope_format(user_interface),
+    };
 
     println!("combine: creating pdf");
     let filename = "output_versand.pdf";
     let doc_generated = pdfgen::generate_couverts(
         &mut couvert_infos,
         Some(user_interface),
+        &printing_parameters.sort_order.description(),
+        printing_parameters.group_separator,
+        envelope_format,
+        &layout,
+        &fonts,
         printing_parameters.print_sidebadges,
         printing_parameters.print_groups,
         printing_parameters.print_names,
@@ -61,6 +188,71 @@ pub fn main(user_interface: &dyn ui::UserInteractor, printing_parameters: &Print
     doc_generated
         .save(&mut outfile)
         .expect("Failed to save file...");
+    Ok(())
+}
+
+/// The format from the `envelope` section of config.yaml. Runs from a snapshot may not have a
+/// config.yaml, then the default is used without complaining.
+#[cfg(not(target_arch = "wasm32"))]
+fn load_envelope_format(user_interface: &dyn ui::UserInteractor) -> EnvelopeFormat {
+    if !std::path::Path::new(dbparse::CONFIG_YAML_FILE).exists() {
+        return EnvelopeFormat::default();
+    }
+    let config_error = |reason: String| dbparse::Error::Config {
+        filename: dbparse::CONFIG_YAML_FILE.to_string(),
+        reason: reason,
+    };
+    match dbparse::read_config_section::<pdfgen::EnvelopeConfig>("envelope") {
+        Ok(None) => EnvelopeFormat::default(),
+        Ok(Some(config)) => config.to_format().unwrap_or_else(|reason| {
+            user_interface.error_dbparse(&config_error(format!("envelope: {}", reason)));
+            EnvelopeFormat::default()
+        }),
+        Err(e) => {
+            user_interface.error_dbparse(&e);
+            EnvelopeFormat::default()
+        },
+    }
+}
+
+/// The sheet of a preset, or the `labels` section of config.yaml, which must exist if it was asked
+/// for.
+#[cfg(not(target_arch = "wasm32"))]
+fn load_label_sheet(
+    label_format: LabelFormat,
+    user_interface: &dyn ui::UserInteractor,
+) -> Result<pdfgen::LabelSheet, dbparse::Error> {
+    if let Some(sheet) = label_format.sheet() {
+        return Ok(sheet);
+    }
+    let sheet = dbparse::read_config_section::<pdfgen::LabelSheet>("labels").and_then(|sheet| {
+        let sheet = sheet.ok_or_else(|| dbparse::Error::Config {
+            filename: dbparse::CONFIG_YAML_FILE.to_string(),
+            reason: String::from("`--labels config` needs a `labels` section"),
+        })?;
+        sheet.validate().map_err(|reason| dbparse::Error::Config {
+            filename: dbparse::CONFIG_YAML_FILE.to_string(),
+            reason: format!("`labels`: {}", reason),
+        })?;
+        return Ok(sheet);
+    });
+    if let Err(e) = &sheet {
+        user_interface.error_dbparse(e);
+    }
+    return sheet;
+}
+
+/// downloads the database responses into `snapshot_path`, so that they can be used later with
+/// `PrintingParameters::from_snapshot`
+#[cfg(not(target_arch = "wasm32"))]
+pub fn fetch_snapshot(
+    user_interface: &dyn ui::UserInteractor,
+    snapshot_path: &str,
+) -> Result<usize, dbparse::Error> {
+    let dbparse_interactor = DbparseRedirector {
+        user_interface: Some(user_interface),
+    };
+    dbparse::fetch_snapshot(&dbparse_interactor, snapshot_path)
 }
 
 #[cfg(target_arch = "wasm32")]
@@ -68,12 +260,29 @@ pub fn main() {
     println!("combine: main() not implemented for wasm32");
 }
 
+/// the settings from the yaml files that the user can edit next to config.yaml
+struct UserSettings {
+    role_translation: roletranslation::RoleTranslation,
+    group_priorities: grouppriority::GroupPriorities,
+    households: household::HouseholdSettings,
+}
+
 /// A builder for cosmetic arguments
 pub struct PrintingParameters {
     print_sidebadges: bool,
     print_groups: bool,
     print_names: bool,
     merge_flatmates: bool,
+    snapshot: Option<String>,
+    telemetry: bool,
+    dry_run: bool,
+    sort_order: SortOrder,
+    group_separator: GroupSeparator,
+    envelope_format: Option<EnvelopeFormat>,
+    label_format: Option<LabelFormat>,
+    label_offset: usize,
+    letter_window: Option<WindowPosition>,
+    letter_template: Option<LetterTemplate>,
 }
 impl PrintingParameters {
     pub fn new() -> Self {
@@ -82,6 +291,16 @@ impl PrintingParameters {
             print_groups: true,
             print_names: true,
             merge_flatmates: true,
+            snapshot: None,
+            telemetry: true,
+            dry_run: false,
+            sort_order: SortOrder::default(),
+            group_separator: GroupSeparator::default(),
+            envelope_format: None,
+            label_format: None,
+            label_offset: 0,
+            letter_window: None,
+            letter_template: None,
         }
     }
 
@@ -104,73 +323,135 @@ impl PrintingParameters {
         self.merge_flatmates = b;
         self
     }
+
+    /// read the people from a snapshot file instead of the database. `None` means database.
+    pub fn from_snapshot(mut self, snapshot_path: Option<String>) -> Self {
+        self.snapshot = snapshot_path;
+        self
+    }
+
+    /// `false` disables telemetry even if it is enabled in config.yaml
+    pub fn telemetry(mut self, b: bool) -> Self {
+        self.telemetry = b;
+        self
+    }
+
+    /// only show the report of what would be printed, without creating the pdf
+    pub fn dry_run(mut self, b: bool) -> Self {
+        self.dry_run = b;
+        self
+    }
+
+    /// the order of the couverts in the pdf, by group if not set
+    pub fn sort_order(mut self, sort_order: SortOrder) -> Self {
+        self.sort_order = sort_order;
+        self
+    }
+
+    /// marks where the group of the first receiver changes, nothing if not set
+    pub fn group_separator(mut self, group_separator: GroupSeparator) -> Self {
+        self.group_separator = group_separator;
+        self
+    }
+
+    /// `None` means the `envelope` section of config.yaml decides, and C5 if there is none
+    pub fn envelope_format(mut self, envelope_format: Option<EnvelopeFormat>) -> Self {
+        self.envelope_format = envelope_format;
+        self
+    }
+
+    /// prints address labels on sheets of this format instead of couverts if set
+    pub fn label_format(mut self, label_format: Option<LabelFormat>) -> Self {
+        self.label_format = label_format;
+        self
+    }
+
+    /// how many labels of the first sheet are already used
+    pub fn label_offset(mut self, label_offset: usize) -> Self {
+        self.label_offset = label_offset;
+        self
+    }
+
+    /// prints A4 letters for window couverts with the window at this position instead of couverts
+    /// if set
+    pub fn letter_window(mut self, window: Option<WindowPosition>) -> Self {
+        self.letter_window = window;
+        self
+    }
+
+    /// the text of the letters. Without it, the letters only have the address.
+    pub fn letter_template(mut self, template: Option<LetterTemplate>) -> Self {
+        self.letter_template = template;
+        self
+    }
 }
 
 fn merge_households<'b>(
     people: &'b mut Vec<dbparse::ReasonablePerson>,
     mapping: &dbparse::mapping::GroupMapping,
+    settings: &UserSettings,
     user_interface: &dyn ui::UserInteractor,
+    run_report: &mut report::RunReport,
     normalize_but_dont_merge: bool,
 ) -> Vec<pdfgen::CouvertInfo> {
-    assert!(people.len() > 0);
-
     // normalize entries in each person so that we can sort
     for person in people.iter_mut() {
-        /* Person
-        first_name: String
-        last_name: String
-        nickname: String
-        address: String
-        zip_code: String
-        town: String
-        name_parents: String
-        roles: HashSet<Role>
-        groups: HashSet<ReasonableGroup>*/
-
-        person.address = normalize_address(&person.address);
+        person.address.street = normalize_address(&person.address.street);
         warn_if_address_incomplete(&person, user_interface);
-        person.town = normalize_town(&person.town);
+        person.address.town = normalize_town(&person.address.town);
     }
 
-    // sort people be zip, town, last name
+    // sort people by zip, town, last name, street
     people.sort_by(|a, b| {
-        a.zip_code
-            .cmp(&b.zip_code)
-            .then(a.town.cmp(&b.town))
+        a.address
+            .zip_code
+            .cmp(&b.address.zip_code)
+            .then(a.address.town.cmp(&b.address.town))
             .then(a.last_name.cmp(&b.last_name))
+            .then(a.address.street_key().cmp(&b.address.street_key()))
+            .then(a.address.house_number.cmp(&b.address.house_number))
+            .then(a.address.addition.cmp(&b.address.addition))
     });
 
     // look for people that live in the same place
-    let mut couvert_infos: Vec<pdfgen::CouvertInfo> = Vec::with_capacity(people.len());
-    let first_person: &dbparse::ReasonablePerson = &people.get(0).unwrap();
-    let mut couvert_info: pdfgen::CouvertInfo = pdfgen::CouvertInfo {
-        receivers: Vec::<pdfgen::Receiver>::new(),
-        address: get_address(first_person, /*use family:*/ false),
-    };
-    couvert_info
-        .receivers
-        .push(into_receiver(first_person, &mapping));
-    couvert_infos.push(couvert_info);
-    let mut previous_family_address = get_address(first_person, false);
-
-    for person in people.iter().skip(1) {
-        let addr_family = get_address(person, true);
-        let receiver = into_receiver(person, &mapping);
-
-        if addr_family == previous_family_address && !normalize_but_dont_merge {
-            // add to previous couvert another receiver
-            couvert_infos.last_mut().unwrap().receivers.push(receiver);
-            couvert_infos.last_mut().unwrap().address = addr_family;
-        } else {
-            let mut couvert_info: pdfgen::CouvertInfo = pdfgen::CouvertInfo {
-                receivers: Vec::<pdfgen::Receiver>::new(),
-                address: get_address(person, /*family:*/ false),
-            };
-            couvert_info.receivers.push(receiver);
-            couvert_infos.push(couvert_info);
-
-            previous_family_address = addr_family.clone();
+    let clustering = if normalize_but_dont_merge {
+        household::Clustering {
+            households: (0..people.len()).map(|i| vec![i]).collect(),
+            borderline_pairs: Vec::new(),
+            overrides: Vec::new(),
+            missing_ids: Vec::new(),
         }
+    } else {
+        household::cluster(people, &settings.households)
+    };
+    run_report.add_section(overrides_section(people, &clustering));
+    run_report.add_section(borderline_section(people, &clustering.borderline_pairs));
+    let households = clustering.households;
+
+    let today = chrono::Local::now().naive_local().date();
+    let mut couvert_infos: Vec<pdfgen::CouvertInfo> = Vec::with_capacity(households.len());
+    for household in households.iter() {
+        let members: Vec<&dbparse::ReasonablePerson> =
+            household.iter().map(|&i| &people[i]).collect();
+        let addressees: Vec<addressee::Addressee> = members
+            .iter()
+            .map(|member| {
+                addressee::addressee_of(member, &settings.role_translation.addressees, today)
+            })
+            .collect();
+        // the more specific address wins, e.g. "Hauptstrasse 5a" over "Hauptstr 5"
+        let address_source: &dbparse::ReasonablePerson = members
+            .iter()
+            .find(|member| !member.address.addition.is_empty())
+            .unwrap_or(&members[0]);
+        couvert_infos.push(pdfgen::CouvertInfo {
+            receivers: members
+                .iter()
+                .map(|member| into_receiver(member, &mapping, settings))
+                .collect(),
+            address: get_address(address_source, name_line(&members, &addressees)),
+            country: country::country_line(&address_source.address),
+        });
     }
 
     // sort by nickname within household
@@ -207,9 +488,7 @@ fn warn_if_address_incomplete(
 ) -> bool {
     let issue: bool = person.first_name.is_empty()
         || person.last_name.is_empty()
-        || person.address.is_empty()
-        || person.zip_code.is_empty()
-        || person.town.is_empty();
+        || !person.address.is_complete();
 
     if issue {
         user_interface.report_bad_address(person);
@@ -240,36 +519,71 @@ pub fn normalize_town(town: &String) -> String {
     );
 }
 
-fn get_address(
-    person: &dbparse::ReasonablePerson,
-    use_familie_instead_of_first_name: bool,
-) -> Vec<String> {
-    let first_or_family = if use_familie_instead_of_first_name {
-        String::from("Familie")
-    } else {
-        person.first_name.clone()
-    };
-    vec![
-        format!("{} {}", first_or_family, person.last_name),
-        person.address.clone(),
-        format!("{} {}", person.zip_code, person.town),
-    ]
+/// "Anna Muster" for a single person, "Familie Muster" for a household, and "Familie Muster /
+/// Beispiel" for households with several last names.
+///
+/// `addressees` belong to `members` in the same order. If all members are to be addressed to
+/// their parents, and they have the same parents, the couvert goes to the parents.
+fn name_line(
+    members: &Vec<&dbparse::ReasonablePerson>,
+    addressees: &Vec<addressee::Addressee>,
+) -> String {
+    if addressees
+        .iter()
+        .all(|&a| a == addressee::Addressee::Parents)
+    {
+        let mut parents_lines: Vec<String> =
+            members.iter().map(|m| addressee::parents_line(m)).collect();
+        parents_lines.dedup();
+        if parents_lines.len() == 1 {
+            return parents_lines.remove(0);
+        }
+    }
+    if members.len() == 1 && addressees[0] == addressee::Addressee::Person {
+        return format!("{} {}", members[0].first_name, members[0].last_name);
+    }
+    let mut last_names: Vec<&str> = Vec::new();
+    for member in members.iter() {
+        if !last_names.contains(&member.last_name.as_str()) {
+            last_names.push(&member.last_name);
+        }
+    }
+    return format!("Familie {}", last_names.join(" / "));
+}
+
+fn get_address(person: &dbparse::ReasonablePerson, name_line: String) -> Vec<String> {
+    let address = &person.address;
+    let mut lines = vec![name_line];
+    lines.extend(address.recipient_lines.iter().cloned());
+    let street_line = address.street_line();
+    if !street_line.is_empty() {
+        lines.push(street_line);
+    }
+    if !address.postbox.is_empty() {
+        lines.push(address.postbox.clone());
+    }
+    lines.extend(country::town_lines(address));
+    return lines;
 }
 
 fn into_receiver(
     person: &dbparse::ReasonablePerson,
     group_mapping: &dbparse::mapping::GroupMapping,
+    settings: &UserSettings,
 ) -> pdfgen::Receiver {
-    let pdfgen_roles = person
-        .roles
-        .iter()
-        .map(|x| roletranslation::role_to_role(x));
+    let pdfgen_roles = person.roles.iter().map(|x| {
+        roletranslation::role_to_role(
+            x,
+            roletranslation::group_of_role(person, x),
+            &settings.role_translation,
+        )
+    });
     let mut best_pdfgen_role: pdfgen::Role = pdfgen_roles
         .max_by_key(|x| x.priority())
         .unwrap_or(pdfgen::Role::Nothing);
 
     let best_group_perhaps: Option<&dbparse::ReasonableGroup> =
-        person.groups.iter().max_by_key(|x| x.priority());
+        best_group(person, &settings.group_priorities);
     let display_name = match best_group_perhaps {
         Some(group) => group_mapping
             .get_display_name(&group.inner_group.id)
@@ -283,11 +597,6 @@ fn into_receiver(
         false => person.nickname.clone(),
     };
 
-    // if the person is of group Trägerkreis, make them a Trägerkreis Role
-    if let Some(_index) = display_name.find("Trägerkreis") {
-        best_pdfgen_role = pdfgen::Role::Traegerkreis;
-    }
-
     // replace Role::Nothing with the name of the person
     if let pdfgen::Role::Nothing = best_pdfgen_role {
         best_pdfgen_role = pdfgen::Role::Custom(name.clone());
@@ -322,44 +631,138 @@ impl Prioritized for pdfgen::Role {
     }
 }
 
-impl Prioritized for dbparse::ReasonableGroup {
-    /// Higher priority for more specific groups
-    /// I try to use distinct priorities, so that the same dataset will always produce the same
-    /// output. That does not hold for non-matches `_`.
-    fn priority(&self) -> Priority {
-        match self.inner_group.group_type.as_str() {
-            "Dachverband" => Priority(10),
-            "Mitgliederorganisation" => Priority(30),
-            "Sektion" => Priority(40),
-            "Verein" => Priority(45),
-            "Jungschar" => Priority(49),
-            "Gruppe" => Priority(50),     // M oder F
-            "Ortsgruppe" => Priority(60), // Pfä-Feh-Hi-Rus
-            "Stufe" => Priority(70),
-            "Mitglieder" => Priority(71),
-            //--- end useless stuff ---
-            "Ten-Sing" => Priority(80),
-            "Gremium" => Priority(81), // e.g. "C-Gruppe", "Cevi Plus Team"
-            "Externe" => match self.inner_group.name.as_str() {
-                "Trägerkreis Mitglieder" => Priority(89),
-                "J+S-Coaches" => Priority(88),
-                "Leiter ehemalig" => Priority(87),
-                "Ehemalige" => Priority(87),
-                "Gebetsbrunch" => Priority(86),
-                "C-Newsletter" => Priority(85),
-                "Freie Mitarbeiter" => Priority(84),
-                "Z_Import Optigem" => Priority(1),
-                "Admin GS 2019" => Priority(3),
-                "EXT: Y-Card Aktiv und Gültig" => Priority(2),
-                _ => Priority(83),
+/// the group that is printed on the couvert
+fn best_group<'a>(
+    person: &'a dbparse::ReasonablePerson,
+    group_priorities: &grouppriority::GroupPriorities,
+) -> Option<&'a dbparse::ReasonableGroup> {
+    person
+        .groups
+        .iter()
+        .max_by_key(|x| group_priorities.priority(x))
+}
+
+/// one line per person: which group was chosen, with which priority, and why
+fn group_choice_section(
+    people: &Vec<dbparse::ReasonablePerson>,
+    group_mapping: &dbparse::mapping::GroupMapping,
+    group_priorities: &grouppriority::GroupPriorities,
+) -> report::ReportSection {
+    let mut section = report::ReportSection::new("Gedruckte Gruppe pro Person");
+    for person in people.iter() {
+        let line = match best_group(person, group_priorities) {
+            Some(group) => {
+                let (priority, source) = group_priorities.resolve(group);
+                format!(
+                    "{} {} (id {}): {} [id {}, {}] Priorität {} aus {}",
+                    person.first_name,
+                    person.last_name,
+                    person.id,
+                    group_mapping
+                        .get_display_name(&group.inner_group.id)
+                        .unwrap_or_else(|| group.inner_group.name.clone()),
+                    group.inner_group.id,
+                    group.inner_group.group_type,
+                    priority,
+                    source
+                )
             },
-            "Vorstand" => Priority(90), // if somebody is in a group and in vorstand, we want the group
-            "Untergruppe" => Priority(100), // We want always this. It's e.g. Holon.
-            "Fröschli" => Priority(100), // Whyever this exists
-            // if something is not in this list, we don't want it in almost all cases
-            _ => Priority(0),
-        }
+            None => format!(
+                "{} {} (id {}): keine Gruppe",
+                person.first_name, person.last_name, person.id
+            ),
+        };
+        section.add_line(line);
+    }
+    return section;
+}
+
+fn describe_person(person: &dbparse::ReasonablePerson) -> String {
+    format!(
+        "{} {} (id {}), {}",
+        person.first_name,
+        person.last_name,
+        person.id,
+        person.address.street_line()
+    )
+}
+
+/// who was dropped because of exclude.yaml, and which of its entries did nothing
+fn exclusions_section(exclusions: &exclusion::Exclusions) -> report::ReportSection {
+    let mut section = report::ReportSection::new("Ausgeschlossen durch exclude.yaml");
+    for (person, reason) in exclusions.excluded.iter() {
+        section.add_line(format!("{}: {}", describe_person(person), reason));
+    }
+    for entry in exclusions.unused_entries.iter() {
+        section.add_line(format!("nicht angewendet, {}", entry));
     }
+    return section;
+}
+
+/// texts with letters that their font does not have, so they would be printed as boxes
+fn glyphs_section(problems: Vec<String>) -> report::ReportSection {
+    let mut section = report::ReportSection::new("Fehlende Schriftzeichen");
+    for problem in problems {
+        section.add_line(problem);
+    }
+    return section;
+}
+
+/// what households.yaml changed, and which of its ids do not exist
+fn overrides_section(
+    people: &Vec<dbparse::ReasonablePerson>,
+    clustering: &household::Clustering,
+) -> report::ReportSection {
+    use household::AppliedOverride;
+    let mut section = report::ReportSection::new("Angewendet aus households.yaml");
+    for applied in clustering.overrides.iter() {
+        section.add_line(match applied {
+            AppliedOverride::Together(members) => format!(
+                "together: {}",
+                members
+                    .iter()
+                    .map(|&i| describe_person(&people[i]))
+                    .collect::<Vec<String>>()
+                    .join(" + ")
+            ),
+            AppliedOverride::Apart(first, second) => format!(
+                "apart: {} / {}",
+                describe_person(&people[*first]),
+                describe_person(&people[*second])
+            ),
+            AppliedOverride::Conflict(first, second) => format!(
+                "Widerspruch, sowohl in together als auch in apart, together gewinnt: {} / {}",
+                describe_person(&people[*first]),
+                describe_person(&people[*second])
+            ),
+        });
+    }
+    for id in clustering.missing_ids.iter() {
+        section.add_line(format!(
+            "id {} existiert nicht (mehr) in der Datenbank und wurde ignoriert",
+            id
+        ));
+    }
+    return section;
+}
+
+/// one line per pair of people that might live together, but were not put on the same couvert
+fn borderline_section(
+    people: &Vec<dbparse::ReasonablePerson>,
+    borderline_pairs: &Vec<household::BorderlinePair>,
+) -> report::ReportSection {
+    let mut section = report::ReportSection::new(
+        "Unsichere Haushalte (nicht zusammengelegt, bitte prüfen)",
+    );
+    for pair in borderline_pairs.iter() {
+        section.add_line(format!(
+            "{:.2}: {} / {} (in households.yaml unter together oder apart festlegen)",
+            pair.score,
+            describe_person(&people[pair.first]),
+            describe_person(&people[pair.second])
+        ));
+    }
+    return section;
 }
 
 /// this exists solely to avoid cyclic dependencies from ui to dbparse and back
@@ -380,6 +783,27 @@ impl<'a> dbparse::DbparseInteractor for DbparseRedirector<'a> {
             Some(ui) => ui.error_missing_config_file(filename),
         }
     }
+
+    fn on_pages_fetched(&self, endpoint_number: usize, pages: usize) {
+        match self.user_interface {
+            None => (),
+            Some(ui) => ui.on_pages_fetched(endpoint_number, pages),
+        }
+    }
+
+    fn on_telemetry_finished(&self, result: Result<(), &dyn std::error::Error>) {
+        match self.user_interface {
+            None => (),
+            Some(ui) => ui.on_telemetry_finished(result),
+        }
+    }
+
+    fn on_error(&self, error: &dbparse::Error) {
+        match self.user_interface {
+            None => (),
+            Some(ui) => ui.error_dbparse(error),
+        }
+    }
 }
 
 /// remove ALL settings if remove_config is true, otherwise only remove all files that are not
@@ -454,6 +878,147 @@ pub fn clean(
         });
     }
 
+    // delete roles yaml file
+    if !test_run {
+        let _r: Option<()> = uiopt.and_then(|ui| {
+            ui.inform_user(&*format!(
+                "Removing Roles File: {}",
+                crate::roletranslation::ROLES_YAML_FILE
+            ));
+            None
+        });
+        // see note at the start of this function
+        {
+            let _file = OpenOptions::new()
+                .write(true)
+                .create(true)
+                .open(crate::roletranslation::ROLES_YAML_FILE);
+        }
+        std::fs::remove_file(crate::roletranslation::ROLES_YAML_FILE)?;
+    } else {
+        let _r: Option<()> = uiopt.and_then(|ui| {
+            ui.inform_user(&*format!(
+                "Would remove Roles File: {}",
+                crate::roletranslation::ROLES_YAML_FILE
+            ));
+            None
+        });
+    }
+
+    // delete group priorities yaml file
+    if !test_run {
+        let _r: Option<()> = uiopt.and_then(|ui| {
+            ui.inform_user(&*format!(
+                "Removing Group Priorities File: {}",
+                crate::grouppriority::GROUP_PRIORITIES_YAML_FILE
+            ));
+            None
+        });
+        // see note at the start of this function
+        {
+            let _file = OpenOptions::new()
+                .write(true)
+                .create(true)
+                .open(crate::grouppriority::GROUP_PRIORITIES_YAML_FILE);
+        }
+        std::fs::remove_file(crate::grouppriority::GROUP_PRIORITIES_YAML_FILE)?;
+    } else {
+        let _r: Option<()> = uiopt.and_then(|ui| {
+            ui.inform_user(&*format!(
+                "Would remove Group Priorities File: {}",
+                crate::grouppriority::GROUP_PRIORITIES_YAML_FILE
+            ));
+            None
+        });
+    }
+
+    // delete households yaml file
+    if !test_run {
+        let _r: Option<()> = uiopt.and_then(|ui| {
+            ui.inform_user(&*format!(
+                "Removing Households File: {}",
+                crate::household::HOUSEHOLDS_YAML_FILE
+            ));
+            None
+        });
+        // see note at the start of this function
+        {
+            let _file = OpenOptions::new()
+                .write(true)
+                .create(true)
+                .open(crate::household::HOUSEHOLDS_YAML_FILE);
+        }
+        std::fs::remove_file(crate::household::HOUSEHOLDS_YAML_FILE)?;
+        // create households file template
+        crate::household::create_households_yaml_file_template()?;
+    } else {
+        let _r: Option<()> = uiopt.and_then(|ui| {
+            ui.inform_user(&*format!(
+                "Would remove Households File: {}",
+                crate::household::HOUSEHOLDS_YAML_FILE
+            ));
+            None
+        });
+    }
+
+    // delete exclude yaml file
+    if !test_run {
+        let _r: Option<()> = uiopt.and_then(|ui| {
+            ui.inform_user(&*format!(
+                "Removing Exclude File: {}",
+                crate::exclusion::EXCLUDE_YAML_FILE
+            ));
+            None
+        });
+        // see note at the start of this function
+        {
+            let _file = OpenOptions::new()
+                .write(true)
+                .create(true)
+                .open(crate::exclusion::EXCLUDE_YAML_FILE);
+        }
+        std::fs::remove_file(crate::exclusion::EXCLUDE_YAML_FILE)?;
+        // create exclude file template
+        crate::exclusion::create_exclude_yaml_file_template()?;
+    } else {
+        let _r: Option<()> = uiopt.and_then(|ui| {
+            ui.inform_user(&*format!(
+                "Would remove Exclude File: {}",
+                crate::exclusion::EXCLUDE_YAML_FILE
+            ));
+            None
+        });
+    }
+
+    // delete layout yaml file
+    if !test_run {
+        let _r: Option<()> = uiopt.and_then(|ui| {
+            ui.inform_user(&*format!(
+                "Removing Layout File: {}",
+                pdfgen::layout::LAYOUT_YAML_FILE
+            ));
+            None
+        });
+        // see note at the start of this function
+        {
+            let _file = OpenOptions::new()
+                .write(true)
+                .create(true)
+                .open(pdfgen::layout::LAYOUT_YAML_FILE);
+        }
+        std::fs::remove_file(pdfgen::layout::LAYOUT_YAML_FILE)?;
+        // create layout file template
+        pdfgen::layout::create_layout_yaml_file_template()?;
+    } else {
+        let _r: Option<()> = uiopt.and_then(|ui| {
+            ui.inform_user(&*format!(
+                "Would remove Layout File: {}",
+                pdfgen::layout::LAYOUT_YAML_FILE
+            ));
+            None
+        });
+    }
+
     // delete config.yaml file
     if !test_run {
         if remove_config {
@@ -489,4 +1054,141 @@ pub fn clean(
 }
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    fn person(
+        id: usize,
+        first_name: &str,
+        last_name: &str,
+        street_line: &str,
+    ) -> dbparse::ReasonablePerson {
+        dbparse::ReasonablePerson {
+            id: id,
+            first_name: String::from(first_name),
+            last_name: String::from(last_name),
+            address: dbparse::PostalAddress::parse(street_line, "8330", "Pfäffikon ZH"),
+            ..dbparse::ReasonablePerson::default()
+        }
+    }
+
+    fn merge(people: &mut Vec<dbparse::ReasonablePerson>) -> Vec<pdfgen::CouvertInfo> {
+        super::merge_households(
+            people,
+            &dbparse::mapping::GroupMapping::new(),
+            &super::UserSettings {
+                role_translation: super::roletranslation::RoleTranslation::default(),
+                group_priorities: super::grouppriority::GroupPriorities::default(),
+                households: super::household::HouseholdSettings::default(),
+            },
+            &ui::CliUi {},
+            &mut super::report::RunReport::new(),
+            false,
+        )
+    }
+
+    #[test]
+    fn test_everybody_excluded() {
+        let mut people = vec![
+            person(1, "Anna", "Muster", "Hauptstrasse 5"),
+            person(2, "Beat", "Muster", "Hauptstrasse 7"),
+        ];
+        let list = super::exclusion::ExclusionList {
+            people: people
+                .iter()
+                .map(|person| super::exclusion::ExcludedPerson {
+                    id: Some(person.id),
+                    ..super::exclusion::ExcludedPerson::default()
+                })
+                .collect(),
+            groups: Vec::new(),
+        };
+        let exclusions = super::exclusion::apply(&mut people, &list);
+
+        assert_eq!(exclusions.excluded.len(), 2);
+        assert!(merge(&mut people).is_empty());
+    }
+
+    #[test]
+    fn test_merge_households_despite_spelling() {
+        let mut people = vec![
+            person(1, "Anna", "Muster", "Hauptstrasse 5"),
+            person(2, "Beat", "Muster", "Hauptstr 5a"),
+            person(3, "Carla", "Muster", "Hauptstrasse 7"),
+        ];
+        let couverts = merge(&mut people);
+
+        assert_eq!(couverts.len(), 2);
+        let family = couverts
+            .iter()
+            .find(|c| c.receivers.len() == 2)
+            .expect("Anna and Beat should share a couvert");
+        assert_eq!(
+            family.address,
+            vec!["Familie Muster", "Hauptstr 5a", "8330 Pfäffikon ZH"]
+        );
+    }
+
+    #[test]
+    fn test_foreign_couvert_gets_country_line() {
+        let mut anna = person(1, "Anna", "Muster", "Hauptstrasse 5");
+        anna.address.country = Some(String::from("DE"));
+        let mut beat = person(2, "Beat", "Muster", "Hauptstrasse 5");
+        beat.address.country = Some(String::from("CH"));
+        let mut people = vec![anna, beat];
+        let couverts = merge(&mut people);
+
+        // the country differs, so they are not merged
+        assert_eq!(couverts.len(), 2);
+        let countries: Vec<Option<String>> = couverts.iter().map(|c| c.country.clone()).collect();
+        assert!(countries.contains(&Some(String::from("DEUTSCHLAND"))));
+        assert!(countries.contains(&None));
+    }
+
+    #[test]
+    fn test_postbox_and_care_of_lines() {
+        // the order of the lines in the database does not matter
+        for lines in vec!["c/o Beispiel\nPostfach 12", "Postfach 12\nc/o Beispiel"] {
+            let mut people = vec![person(1, "Anna", "Muster", lines)];
+            let couverts = merge(&mut people);
+            assert_eq!(
+                couverts[0].address,
+                vec![
+                    "Anna Muster",
+                    "c/o Beispiel",
+                    "Postfach 12",
+                    "8330 Pfäffikon ZH"
+                ],
+                "{}",
+                lines
+            );
+        }
+    }
+
+    #[test]
+    fn test_minor_teilnehmer_couvert_goes_to_parents() {
+        use chrono::Datelike;
+        let mut anna = person(1, "Anna", "Muster", "Hauptstrasse 5");
+        anna.nickname = String::from("Fiona");
+        anna.salutation_parents = String::from("Herr und Frau");
+        anna.name_parents = String::from("Hans und Maria Muster");
+        anna.birthday = format!("{}-01-01", chrono::Local::now().year() - 10);
+        anna.roles.insert(dbparse::Role::new(
+            std::rc::Rc::<str>::from("1"),
+            String::from("Teilnehmer/-in"),
+            None,
+            String::from("1341"),
+            String::from("115"),
+        ));
+        let mut people = vec![anna];
+        let couverts = merge(&mut people);
+
+        assert_eq!(
+            couverts[0].address,
+            vec![
+                "Herr und Frau Hans und Maria Muster",
+                "Hauptstrasse 5",
+                "8330 Pfäffikon ZH"
+            ]
+        );
+        assert_eq!(couverts[0].receivers[0].nickname, "Fiona");
+    }
+}
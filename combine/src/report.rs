@@ -0,0 +1,67 @@
+// The report collects what combine decided on the way, so that the user can check it before
+// printing hundreds of couverts. It is shown through the UserInteractor.
+
+/// everything worth telling the user about one run, in sections
+#[derive(Debug, Default)]
+pub struct RunReport {
+    pub sections: Vec<ReportSection>,
+}
+
+#[derive(Debug)]
+pub struct ReportSection {
+    pub title: String,
+    pub lines: Vec<String>,
+}
+
+impl ReportSection {
+    pub fn new(title: &str) -> Self {
+        ReportSection {
+            title: title.to_string(),
+            lines: Vec::new(),
+        }
+    }
+
+    pub fn add_line(&mut self, line: String) {
+        self.lines.push(line);
+    }
+}
+
+impl RunReport {
+    pub fn new() -> Self {
+        RunReport::default()
+    }
+
+    pub fn add_section(&mut self, section: ReportSection) {
+        self.sections.push(section);
+    }
+
+    /// plain text, one line per entry, sections separated by an empty line.
+    /// Empty sections are left out.
+    pub fn render(&self) -> String {
+        let mut rendered = String::new();
+        for section in self.sections.iter().filter(|s| !s.lines.is_empty()) {
+            if !rendered.is_empty() {
+                rendered.push('\n');
+            }
+            rendered.push_str(&format!("== {} ==\n", section.title));
+            for line in section.lines.iter() {
+                rendered.push_str(&format!("  {}\n", line));
+            }
+        }
+        return rendered;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_render_skips_empty_sections() {
+        let mut report = super::RunReport::new();
+        report.add_section(super::ReportSection::new("Leer"));
+        let mut section = super::ReportSection::new("Gruppen");
+        section.add_line(String::from("Anna Muster: Holon"));
+        report.add_section(section);
+
+        assert_eq!(report.render(), "== Gruppen ==\n  Anna Muster: Holon\n");
+    }
+}
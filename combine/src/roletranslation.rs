@@ -1,46 +1,271 @@
 use dbparse;
 use pdfgen;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::read_to_string;
+use std::io::Write;
+
 // create map of all roles from db
 // auto-map to roles from pdfgen (Leiter, Teilnehmer, Traegerkreis, Ehemalig)
 // auto-map some roles to be ignored, so that only reasonable roles remain
 // create yaml file so user can specify what other roles should map to what, and what roles to
 // ignore
 //
+// roles.yaml is generated and extended like mapping.yaml: every role_type that shows up in the
+// database gets an entry, new ones with `Nothing`. The user can then change them to any of the
+// pdfgen roles, or to `Custom: "some label"` for a badge with their own text.
 //
-// Or perhaps simply ignore everything that is not obviously
-// Leiter/Teilnehmer/Traegerkreis/Ehemalig. No user interaction required.
+// Some badges cannot be told from the role_type alone: Trägerkreis and Ehemalige are groups, and
+// the people in there are just "Externe/-r" or "Verantwortliche/-r". For those, roles.yaml has a
+// list of `rules` that look at the group of the role as well. The first rule that matches wins,
+// only if none matches, `map` is used.
 //
-pub fn role_to_role(dbrole: &dbparse::Role) -> pdfgen::Role {
-    match dbrole.role_type.as_ref() {
-        "Teilnehmer/-in" => pdfgen::Role::Teilnehmer,
-        //"Traegerkreis" => pdfgen::Role::Traegerkreis, // Trägerkreis is not a role, but a group. Roles would either be "Externe/-r" or "Verantwortliche/-r"
-        "Minigruppenleiter/-in" => pdfgen::Role::Leiter,
-        "Gruppenleiter/-in" => pdfgen::Role::Leiter,
-        "Coach" => pdfgen::Role::Coach,
-        "Abteilungsleiter/-in" => pdfgen::Role::Leiter,
-        "Adressverwalter/-in" => pdfgen::Role::Nothing,
-        "Adressverantwortlicher" => pdfgen::Role::Nothing,
-        "Chorsänger/-in" => pdfgen::Role::Nothing,
-        "Fröschlihauptleiter/-in" => pdfgen::Role::Leiter,
-        "Kassier" => pdfgen::Role::Kassier,
-        "Freie/-r Mitarbeiter/-in" => pdfgen::Role::Nothing,
-        "Hausverantwortliche/-r" => pdfgen::Role::Hausverantwortlicher,
-        "Administrator/-in Cevi DB" => pdfgen::Role::Admin,
-        "Externe/-r" => pdfgen::Role::Nothing, // TODO: how to recognize ehemalige?
-        "Lädeliverantwortliche/-r" => pdfgen::Role::Laedeli,
-        "Mitglied" => pdfgen::Role::Nothing,
-        "Stufenleiter/-in" => pdfgen::Role::Leiter,
-        "Fröschlileiter/-in" => pdfgen::Role::Leiter,
-        "Aktuar/-in" => pdfgen::Role::Aktuar,
-        "Materialverantwortliche/-r" => pdfgen::Role::Matchef,
-        "Verantwortliche/-r" => pdfgen::Role::Nothing,
+// `addressees` says per role_type to whom the couvert is addressed, see addressee.rs.
+
+pub const ROLES_YAML_FILE: &str = "roles.yaml";
+
+/// the content of roles.yaml
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RoleTranslation {
+    /// if the file has no `rules` at all, the default rules are used. `rules: []` disables them.
+    #[serde(default = "default_rules")]
+    pub rules: Vec<BadgeRule>,
+    pub map: BTreeMap<String, pdfgen::Role>,
+    #[serde(default = "crate::addressee::default_addressees")]
+    pub addressees: BTreeMap<String, crate::addressee::Addressee>,
+}
+
+/// A rule matches a role if all the conditions that are set match. A rule without conditions
+/// matches every role.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BadgeRule {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group_type: Option<String>,
+    /// the exact group name as stored in the database
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group_name: Option<String>,
+    /// part of the group name, ignoring upper and lower case
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group_name_contains: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group_id: Option<String>,
+    /// the badge that is printed if the rule matches
+    pub role: pdfgen::Role,
+}
+
+impl BadgeRule {
+    /// `group` is the group in which the person has `dbrole`, if it is known. Rules with
+    /// conditions on the group never match without it.
+    pub fn matches(&self, dbrole: &dbparse::Role, group: Option<&dbparse::Group>) -> bool {
+        if let Some(role_type) = &self.role_type {
+            if *role_type != dbrole.role_type {
+                return false;
+            }
+        }
+        let has_group_condition = self.group_type.is_some()
+            || self.group_name.is_some()
+            || self.group_name_contains.is_some()
+            || self.group_id.is_some();
+        if !has_group_condition {
+            return true;
+        }
+        let group = match group {
+            Some(group) => group,
+            None => return false,
+        };
+        if let Some(group_type) = &self.group_type {
+            if *group_type != group.group_type {
+                return false;
+            }
+        }
+        if let Some(group_name) = &self.group_name {
+            if *group_name != group.name {
+                return false;
+            }
+        }
+        if let Some(part) = &self.group_name_contains {
+            if !group.name.to_lowercase().contains(&part.to_lowercase()) {
+                return false;
+            }
+        }
+        if let Some(group_id) = &self.group_id {
+            if *group_id != group.id {
+                return false;
+            }
+        }
+        return true;
+    }
+}
+
+/// the heuristics that are shipped with the tool. They only look at the roles people have in
+/// Trägerkreis and Ehemalige groups, so that a leader who is also listed there keeps their badge.
+fn default_rules() -> Vec<BadgeRule> {
+    let group_name_contains = |role_type: &str, part: &str, role: pdfgen::Role| BadgeRule {
+        role_type: Some(role_type.to_string()),
+        group_type: None,
+        group_name: None,
+        group_name_contains: Some(part.to_string()),
+        group_id: None,
+        role: role,
+    };
+    let mut rules = Vec::new();
+    for role_type in vec!["Externe/-r", "Verantwortliche/-r"] {
+        // e.g. "Trägerkreis Mitglieder"
+        rules.push(group_name_contains(
+            role_type,
+            "Trägerkreis",
+            pdfgen::Role::Traegerkreis,
+        ));
+        // e.g. "Ehemalige" or "Leiter ehemalig"
+        rules.push(group_name_contains(
+            role_type,
+            "ehemalig",
+            pdfgen::Role::Ehemalige,
+        ));
+    }
+    rules
+}
 
+impl Default for RoleTranslation {
+    /// the table that is shipped with the tool
+    fn default() -> Self {
+        let defaults = vec![
+            ("Teilnehmer/-in", pdfgen::Role::Teilnehmer),
+            //"Traegerkreis" => pdfgen::Role::Traegerkreis, // Trägerkreis is not a role, but a group. Roles would either be "Externe/-r" or "Verantwortliche/-r"
+            ("Minigruppenleiter/-in", pdfgen::Role::Leiter),
+            ("Gruppenleiter/-in", pdfgen::Role::Leiter),
+            ("Coach", pdfgen::Role::Coach),
+            ("Abteilungsleiter/-in", pdfgen::Role::Leiter),
+            ("Adressverwalter/-in", pdfgen::Role::Nothing),
+            ("Adressverantwortlicher", pdfgen::Role::Nothing),
+            ("Chorsänger/-in", pdfgen::Role::Nothing),
+            ("Fröschlihauptleiter/-in", pdfgen::Role::Leiter),
+            ("Kassier", pdfgen::Role::Kassier),
+            ("Freie/-r Mitarbeiter/-in", pdfgen::Role::Nothing),
+            ("Hausverantwortliche/-r", pdfgen::Role::Hausverantwortlicher),
+            ("Administrator/-in Cevi DB", pdfgen::Role::Admin),
+            ("Externe/-r", pdfgen::Role::Nothing), // Ehemalige are recognized by their group, see rules
+            ("Lädeliverantwortliche/-r", pdfgen::Role::Laedeli),
+            ("Mitglied", pdfgen::Role::Nothing),
+            ("Stufenleiter/-in", pdfgen::Role::Leiter),
+            ("Fröschlileiter/-in", pdfgen::Role::Leiter),
+            ("Aktuar/-in", pdfgen::Role::Aktuar),
+            ("Materialverantwortliche/-r", pdfgen::Role::Matchef),
+            ("Verantwortliche/-r", pdfgen::Role::Nothing),
+        ];
+        RoleTranslation {
+            rules: default_rules(),
+            map: defaults
+                .into_iter()
+                .map(|(role_type, role)| (role_type.to_string(), role))
+                .collect(),
+            addressees: crate::addressee::default_addressees(),
+        }
+    }
+}
+
+impl RoleTranslation {
+    /// `None` if no rule matches and the role_type is not in the table
+    pub fn translate(
+        &self,
+        dbrole: &dbparse::Role,
+        group: Option<&dbparse::Group>,
+    ) -> Option<pdfgen::Role> {
+        match self.rules.iter().find(|rule| rule.matches(dbrole, group)) {
+            Some(rule) => Some(rule.role.clone()),
+            None => self.map.get(&dbrole.role_type).cloned(),
+        }
+    }
+
+    /// entries of `self` win over entries of `other`
+    fn merged_with(&self, other: &RoleTranslation) -> RoleTranslation {
+        let mut merged = self.clone();
+        for (role_type, role) in other.map.iter() {
+            merged
+                .map
+                .entry(role_type.clone())
+                .or_insert_with(|| role.clone());
+        }
+        for (role_type, addressee) in other.addressees.iter() {
+            merged
+                .addressees
+                .entry(role_type.clone())
+                .or_insert(*addressee);
+        }
+        merged
+    }
+}
+
+/// `group` is the group in which the person has `dbrole`, see `group_of_role`
+pub fn role_to_role(
+    dbrole: &dbparse::Role,
+    group: Option<&dbparse::Group>,
+    translation: &RoleTranslation,
+) -> pdfgen::Role {
+    match translation.translate(dbrole, group) {
+        Some(role) => role,
         // case where it did not match. That means something new has been added to the DB.
-        &_ => {
-            println!("r2r: don't know what to do with {:?}", dbrole.role_type);
-            return pdfgen::Role::Nothing;
+        // load_role_translation has already told the user about it.
+        None => pdfgen::Role::Nothing,
+    }
+}
+
+/// the group of `person` in which they have `dbrole`
+pub fn group_of_role<'a>(
+    person: &'a dbparse::ReasonablePerson,
+    dbrole: &dbparse::Role,
+) -> Option<&'a dbparse::Group> {
+    person
+        .groups
+        .iter()
+        .map(|group| &group.inner_group)
+        .find(|group| group.id == dbrole.group_id())
+}
+
+/// Loads roles.yaml, adds every role_type of `people` that is not yet in there and stores the
+/// result again. Unknown role types are reported to the user.
+///
+/// If roles.yaml cannot be parsed, it is left untouched and the shipped defaults are used.
+pub fn load_role_translation(
+    people: &Vec<dbparse::ReasonablePerson>,
+    user_interface: &dyn ui::UserInteractor,
+) -> RoleTranslation {
+    let loaded: RoleTranslation = match read_to_string(ROLES_YAML_FILE) {
+        Ok(yaml) => match serde_yaml::from_str::<RoleTranslation>(&yaml) {
+            Ok(translation) => translation,
+            Err(e) => {
+                user_interface.error_loading_roles(&e);
+                return RoleTranslation::default();
+            },
+        },
+        Err(_e) => {
+            println!("combine: no {} yet. Creating it...", ROLES_YAML_FILE);
+            RoleTranslation::default()
         },
+    };
+    let mut translation = loaded.merged_with(&RoleTranslation::default());
+
+    for person in people.iter() {
+        for role in person.roles.iter() {
+            if !translation.map.contains_key(&role.role_type) {
+                user_interface.report_unknown_role(&role.role_type);
+                translation
+                    .map
+                    .insert(role.role_type.clone(), pdfgen::Role::Nothing);
+            }
+        }
     }
+
+    let stored = serde_yaml::to_string(&translation)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
+        .and_then(|yaml| std::fs::File::create(ROLES_YAML_FILE)?.write_all(yaml.as_bytes()));
+    if let Err(e) = stored {
+        user_interface.error_writing_file(ROLES_YAML_FILE, &e);
+    }
+
+    translation
 }
 
 #[cfg(test)]
@@ -59,7 +284,8 @@ mod tests {
             String::from("fake layer group"),
         );
 
-        let pdf_role: pdfgen::Role = super::role_to_role(&db_role);
+        let pdf_role: pdfgen::Role =
+            super::role_to_role(&db_role, None, &super::RoleTranslation::default());
 
         assert_eq!(pdf_role, pdfgen::Role::Teilnehmer);
     }
@@ -78,13 +304,248 @@ mod tests {
             String::from("fake layer group"),
         );
 
-        let pdf_role: pdfgen::Role = super::role_to_role(&db_role);
+        let pdf_role: pdfgen::Role =
+            super::role_to_role(&db_role, None, &super::RoleTranslation::default());
 
         assert_eq!(pdf_role, pdfgen::Role::Nothing);
     }
 
+    #[test]
+    fn test_role_to_role_custom_label_from_yaml() {
+        let yaml = r###"---
+map:
+  Hilfsleiter/-in:
+    Custom: Hilfsleiter
+"###;
+        let translation: super::RoleTranslation = serde_yaml::from_str(yaml).unwrap();
+        let translation = translation.merged_with(&super::RoleTranslation::default());
+        let db_role = dbparse::Role::new(
+            std::rc::Rc::<str>::from("test_id"),
+            String::from("Hilfsleiter/-in"),
+            None,
+            String::from("Fake group id"),
+            String::from("fake layer group"),
+        );
+
+        let pdf_role: pdfgen::Role = super::role_to_role(&db_role, None, &translation);
+
+        assert_eq!(pdf_role, pdfgen::Role::Custom(String::from("Hilfsleiter")));
+        // the defaults are still there
+        assert_eq!(
+            translation.map.get("Teilnehmer/-in"),
+            Some(&pdfgen::Role::Teilnehmer)
+        );
+    }
+
+    fn externe_role(group_id: &str) -> dbparse::Role {
+        dbparse::Role::new(
+            std::rc::Rc::<str>::from("test_id"),
+            String::from("Externe/-r"),
+            None,
+            String::from(group_id),
+            String::from("fake layer group"),
+        )
+    }
+
+    fn group(id: &str, name: &str, group_type: &str) -> dbparse::Group {
+        dbparse::Group {
+            id: id.to_string(),
+            name: name.to_string(),
+            group_type: group_type.to_string(),
+        }
+    }
+
     #[test]
     fn test_role_to_role_traegerkreis() {
-        assert!(false, "please implement");
+        let traegerkreis = group("2300", "Trägerkreis Mitglieder", "Externe");
+        let db_role = externe_role("2300");
+
+        let pdf_role: pdfgen::Role = super::role_to_role(
+            &db_role,
+            Some(&traegerkreis),
+            &super::RoleTranslation::default(),
+        );
+
+        assert_eq!(pdf_role, pdfgen::Role::Traegerkreis);
+    }
+
+    #[test]
+    fn test_role_to_role_ehemalige() {
+        let translation = super::RoleTranslation::default();
+        for name in vec!["Ehemalige", "Leiter ehemalig"] {
+            let ehemalige = group("2301", name, "Externe");
+            let pdf_role =
+                super::role_to_role(&externe_role("2301"), Some(&ehemalige), &translation);
+            assert_eq!(pdf_role, pdfgen::Role::Ehemalige, "group {}", name);
+        }
+    }
+
+    #[test]
+    fn test_leader_in_traegerkreis_keeps_badge() {
+        let traegerkreis = group("2300", "Trägerkreis Mitglieder", "Externe");
+        let leader = dbparse::Role::new(
+            std::rc::Rc::<str>::from("test_id"),
+            String::from("Stufenleiter/-in"),
+            None,
+            String::from("2300"),
+            String::from("fake layer group"),
+        );
+        let verantwortliche = dbparse::Role::new(
+            std::rc::Rc::<str>::from("test_id"),
+            String::from("Verantwortliche/-r"),
+            None,
+            String::from("2300"),
+            String::from("fake layer group"),
+        );
+        let translation = super::RoleTranslation::default();
+
+        assert_eq!(
+            super::role_to_role(&leader, Some(&traegerkreis), &translation),
+            pdfgen::Role::Leiter
+        );
+        assert_eq!(
+            super::role_to_role(&verantwortliche, Some(&traegerkreis), &translation),
+            pdfgen::Role::Traegerkreis
+        );
+    }
+
+    #[test]
+    fn test_role_to_role_group_rule_needs_group() {
+        // without knowing the group, only the role_type counts
+        let pdf_role = super::role_to_role(
+            &externe_role("2300"),
+            None,
+            &super::RoleTranslation::default(),
+        );
+        assert_eq!(pdf_role, pdfgen::Role::Nothing);
+    }
+
+    #[test]
+    fn test_rules_from_yaml() {
+        let yaml = r###"---
+rules:
+  - group_id: "1234"
+    role: Coach
+  - role_type: Externe/-r
+    group_type: Gremium
+    role:
+      Custom: Gremium
+map: {}
+"###;
+        let translation: super::RoleTranslation = serde_yaml::from_str(yaml).unwrap();
+        let role = externe_role("1234");
+
+        // group id
+        let coaches = group("1234", "Irgendwas", "Externe");
+        assert_eq!(
+            super::role_to_role(&role, Some(&coaches), &translation),
+            pdfgen::Role::Coach
+        );
+        // role_type and group_type together
+        let gremium = group("5678", "C-Gruppe", "Gremium");
+        let other_role = externe_role("5678");
+        assert_eq!(
+            super::role_to_role(&other_role, Some(&gremium), &translation),
+            pdfgen::Role::Custom(String::from("Gremium"))
+        );
+        // the first rule wins
+        let coach_gremium = group("1234", "C-Gruppe", "Gremium");
+        assert_eq!(
+            super::role_to_role(&role, Some(&coach_gremium), &translation),
+            pdfgen::Role::Coach
+        );
+        // the default rules are not used when the file has its own
+        let traegerkreis = group("2300", "Trägerkreis Mitglieder", "Externe");
+        assert_eq!(
+            super::role_to_role(&other_role, Some(&traegerkreis), &translation),
+            pdfgen::Role::Nothing
+        );
+    }
+
+    #[test]
+    fn test_missing_rules_use_defaults() {
+        let yaml = r###"---
+map:
+  Externe/-r: Nothing
+"###;
+        let translation: super::RoleTranslation = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(translation.rules, super::default_rules());
+    }
+
+    #[test]
+    fn test_json_api_roles_use_the_default_tables() {
+        // a minor Teilnehmer and a Stufenleiter as the JSON:API endpoint delivers them
+        let body = r###"{
+            "data": [
+                { "id": "6468", "type": "people",
+                  "attributes": { "first_name": "Eric", "last_name": "Mink", "nickname": "Levanzo",
+                                  "street": "Musterstrasse", "housenumber": "2", "zip_code": "8330",
+                                  "town": "Pfäffikon ZH", "birthday": "2012-04-01" },
+                  "relationships": { "roles": { "data": [ { "id": "1", "type": "roles" } ] } } },
+                { "id": "6469", "type": "people",
+                  "attributes": { "first_name": "Anna", "last_name": "Muster", "nickname": "Fiona",
+                                  "street": "Musterstrasse", "housenumber": "4", "zip_code": "8330",
+                                  "town": "Pfäffikon ZH", "birthday": "1990-04-01" },
+                  "relationships": { "roles": { "data": [ { "id": "2", "type": "roles" } ] } } }
+            ],
+            "included": [
+                { "id": "1", "type": "roles",
+                  "attributes": { "type": "Group::Stufe::Teilnehmer", "group_id": 1341 } },
+                { "id": "2", "type": "roles",
+                  "attributes": { "type": "Group::Stufe::Stufenleiter", "group_id": 1341 } },
+                { "id": 1341, "type": "groups",
+                  "attributes": { "name": "Senshi (M)", "type": "Group::Stufe", "layer_group_id": 115 } }
+            ]
+        }"###;
+        let dataset = dbparse::reasonablify_pages(&vec![dbparse::snapshot::SnapshotPage {
+            endpoint_number: 1,
+            format: dbparse::ApiFormat::JsonApi,
+            body: String::from(body),
+        }])
+        .expect("should parse");
+        let translation = super::RoleTranslation::default();
+        let today = chrono::NaiveDate::parse_from_str("2020-01-01", "%Y-%m-%d").unwrap();
+
+        let person = |id: usize| dataset.people.iter().find(|p| p.id == id).unwrap();
+        let badge = |person: &dbparse::ReasonablePerson| {
+            let role = person.roles.iter().next().unwrap();
+            super::role_to_role(role, super::group_of_role(person, role), &translation)
+        };
+        assert_eq!(badge(person(6468)), pdfgen::Role::Teilnehmer);
+        assert_eq!(badge(person(6469)), pdfgen::Role::Leiter);
+        assert_eq!(
+            crate::addressee::addressee_of(person(6468), &translation.addressees, today),
+            crate::addressee::Addressee::Parents
+        );
+        assert_eq!(
+            crate::addressee::addressee_of(person(6469), &translation.addressees, today),
+            crate::addressee::Addressee::Person
+        );
+    }
+
+    #[test]
+    fn test_group_of_role() {
+        let mut person = dbparse::ReasonablePerson {
+            id: 1,
+            first_name: String::from("Eric"),
+            last_name: String::from("Mink"),
+            nickname: String::from("Levanzo"),
+            address: dbparse::PostalAddress::parse("Musterstrasse 2", "8330", "Pfäffikon ZH"),
+            ..dbparse::ReasonablePerson::default()
+        };
+        person.groups.insert(dbparse::ReasonableGroup::from(group(
+            "1",
+            "Holon",
+            "Untergruppe",
+        )));
+        person.groups.insert(dbparse::ReasonableGroup::from(group(
+            "2",
+            "Ehemalige",
+            "Externe",
+        )));
+
+        let found = super::group_of_role(&person, &externe_role("2"));
+        assert_eq!(found.map(|g| g.name.as_str()), Some("Ehemalige"));
+        assert!(super::group_of_role(&person, &externe_role("3")).is_none());
     }
 }
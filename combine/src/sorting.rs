@@ -0,0 +1,208 @@
+use std::cmp::Ordering;
+
+// The order in which the couverts are printed. The post team needs different orders: by group to
+// hand them out in person, by zip code for the "vorsortiert" discount of the Swiss Post, or by
+// town. A sort order is a list of keys, the later keys only decide where the earlier ones are
+// equal. Couverts that are equal in all keys keep the order in which they were created.
+//
+// The keys are read from the command line, e.g. `--sort zip,group`.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    /// the group of the first receiver
+    Group,
+    /// the (Cevi-)name of the first receiver
+    Name,
+    Zip,
+    Town,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SortOrder {
+    keys: Vec<SortKey>,
+}
+
+impl Default for SortOrder {
+    /// by group, as it has always been
+    fn default() -> Self {
+        SortOrder {
+            keys: vec![SortKey::Group],
+        }
+    }
+}
+
+impl std::str::FromStr for SortKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "group" | "gruppe" => Ok(SortKey::Group),
+            "name" => Ok(SortKey::Name),
+            "zip" | "plz" => Ok(SortKey::Zip),
+            "town" | "ort" => Ok(SortKey::Town),
+            other => Err(format!(
+                "unknown sort key {:?}, expected group, name, zip or town",
+                other
+            )),
+        }
+    }
+}
+
+impl std::str::FromStr for SortOrder {
+    type Err = String;
+
+    /// comma separated keys, e.g. "zip,group,name"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let keys = s
+            .split(',')
+            .filter(|key| !key.trim().is_empty())
+            .map(str::parse)
+            .collect::<Result<Vec<SortKey>, String>>()?;
+        if keys.is_empty() {
+            return Err(String::from("at least one sort key is needed"));
+        }
+        Ok(SortOrder { keys: keys })
+    }
+}
+
+impl SortKey {
+    fn description(&self) -> &'static str {
+        match self {
+            SortKey::Group => "Gruppe der ersten Person",
+            SortKey::Name => "(Cevi-)Name der ersten Person",
+            SortKey::Zip => "Postleitzahl",
+            SortKey::Town => "Ort",
+        }
+    }
+
+    fn compare(&self, a: &pdfgen::CouvertInfo, b: &pdfgen::CouvertInfo) -> Ordering {
+        match self {
+            SortKey::Group => group(a).cmp(group(b)),
+            SortKey::Name => nickname(a).to_lowercase().cmp(&nickname(b).to_lowercase()),
+            SortKey::Zip => zip_and_town(a).0.cmp(zip_and_town(b).0),
+            SortKey::Town => zip_and_town(a)
+                .1
+                .to_lowercase()
+                .cmp(&zip_and_town(b).1.to_lowercase()),
+        }
+    }
+}
+
+impl SortOrder {
+    /// for the info page, e.g. "Sortierung der Couverts nach Postleitzahl, dann Ort."
+    pub fn description(&self) -> String {
+        format!(
+            "Sortierung der Couverts nach {}.",
+            self.keys
+                .iter()
+                .map(SortKey::description)
+                .collect::<Vec<&str>>()
+                .join(", dann ")
+        )
+    }
+
+    /// stable, so couverts that are equal in all keys keep their order
+    pub fn sort(&self, couverts: &mut Vec<pdfgen::CouvertInfo>) {
+        couverts.sort_by(|a, b| {
+            self.keys
+                .iter()
+                .fold(Ordering::Equal, |ordering, key| {
+                    ordering.then_with(|| key.compare(a, b))
+                })
+        });
+    }
+}
+
+fn group(couvert: &pdfgen::CouvertInfo) -> &str {
+    couvert.receivers.first().map(|r| r.group.as_str()).unwrap_or("")
+}
+
+fn nickname(couvert: &pdfgen::CouvertInfo) -> &str {
+    couvert
+        .receivers
+        .first()
+        .map(|r| r.nickname.as_str())
+        .unwrap_or("")
+}
+
+/// Zip code and town from the address lines, which is the last line that starts with a digit.
+/// Injected couverts only have their lines, so this works for both. Couverts without such a line
+/// (e.g. to Great Britain) have no zip code and their last line as town.
+fn zip_and_town(couvert: &pdfgen::CouvertInfo) -> (&str, &str) {
+    let town_line = couvert
+        .address
+        .iter()
+        .rev()
+        .find(|line| line.trim_start().starts_with(|c: char| c.is_ascii_digit()));
+    match town_line {
+        Some(line) => {
+            let line = line.trim();
+            match line.find(char::is_whitespace) {
+                Some(end) => (&line[..end], line[end..].trim()),
+                None => (line, ""),
+            }
+        },
+        None => ("", couvert.address.last().map(|l| l.trim()).unwrap_or("")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SortKey, SortOrder};
+
+    fn couvert(group: &str, nickname: &str, town_line: &str) -> pdfgen::CouvertInfo {
+        pdfgen::CouvertInfo {
+            receivers: vec![pdfgen::Receiver {
+                nickname: String::from(nickname),
+                group: String::from(group),
+                role: pdfgen::Role::Teilnehmer,
+            }],
+            address: vec![
+                String::from("Anna Muster"),
+                String::from("Hauptstrasse 5"),
+                String::from(town_line),
+            ],
+            country: None,
+        }
+    }
+
+    fn nicknames(couverts: &Vec<pdfgen::CouvertInfo>) -> Vec<&str> {
+        couverts
+            .iter()
+            .map(|c| c.receivers[0].nickname.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_sort_order() {
+        let order: SortOrder = "zip, group,Name".parse().unwrap();
+        assert_eq!(
+            order.keys,
+            vec![SortKey::Zip, SortKey::Group, SortKey::Name]
+        );
+        assert!("zip,strasse".parse::<SortOrder>().is_err());
+        assert!("".parse::<SortOrder>().is_err());
+        assert_eq!(
+            order.description(),
+            "Sortierung der Couverts nach Postleitzahl, dann Gruppe der ersten Person, dann (Cevi-)Name der ersten Person."
+        );
+    }
+
+    #[test]
+    fn test_sort_by_zip_then_name() {
+        let mut couverts = vec![
+            couvert("Holon", "Zora", "8330 Pfäffikon ZH"),
+            couvert("Skapande", "Fiona", "8335 Hittnau"),
+            couvert("Holon", "Anton", "8330 Pfäffikon ZH"),
+        ];
+        let order: SortOrder = "zip,name".parse().unwrap();
+        order.sort(&mut couverts);
+        assert_eq!(nicknames(&couverts), vec!["Anton", "Zora", "Fiona"]);
+
+        SortOrder::default().sort(&mut couverts);
+        assert_eq!(nicknames(&couverts), vec!["Anton", "Zora", "Fiona"]);
+
+        "town".parse::<SortOrder>().unwrap().sort(&mut couverts);
+        assert_eq!(nicknames(&couverts), vec!["Fiona", "Anton", "Zora"]);
+    }
+}
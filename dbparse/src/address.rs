@@ -0,0 +1,332 @@
+//! Postal addresses, split into their parts so that two spellings of the same address can be
+//! recognized as such, and so that the lines on the couvert can be put together in the right
+//! order.
+//!
+//! hitobito stores addresses in two ways: older versions have a free-text `address` field that may
+//! contain several lines (e.g. "c/o Muster\nHauptstrasse 5"), newer versions have `street`,
+//! `housenumber`, `address_care_of` and `postbox`.
+
+/// the line prefixes that mark a postbox, in the languages of Switzerland
+const POSTBOX_PREFIXES: &[&str] = &[
+    "postfach",
+    "case postale",
+    "casella postale",
+    "postbox",
+    "po box",
+    "p.o. box",
+];
+
+/// the line prefixes that mark a line for someone the couvert is sent in care of, which is never
+/// the street
+const CARE_OF_PREFIXES: &[&str] = &["c/o", "z.h.", "z. h.", "zhd", "p.a.", "chez ", "presso "];
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PostalAddress {
+    /// lines between the name and the street, e.g. "c/o Muster"
+    pub recipient_lines: Vec<String>,
+    pub street: String,
+    /// only the number, e.g. "5" for "Hauptstrasse 5a"
+    pub house_number: String,
+    /// whatever comes after the number, e.g. "a" for "Hauptstrasse 5a"
+    pub addition: String,
+    /// e.g. "Postfach 123"
+    pub postbox: String,
+    pub zip_code: String,
+    pub town: String,
+    /// as delivered by the database, `None` if the database did not say
+    pub country: Option<String>,
+}
+
+impl PostalAddress {
+    /// parses the free-text `address` field of older hitobito versions
+    /// ```
+    /// # use dbparse::PostalAddress;
+    /// let address = PostalAddress::parse("c/o Muster\nHauptstr. 5a\nPostfach 12", "8330", "Pfäffikon ZH");
+    /// assert_eq!(address.recipient_lines, vec!["c/o Muster"]);
+    /// assert_eq!(address.street, "Hauptstr.");
+    /// assert_eq!(address.house_number, "5");
+    /// assert_eq!(address.addition, "a");
+    /// assert_eq!(address.postbox, "Postfach 12");
+    /// ```
+    pub fn parse(address: &str, zip_code: &str, town: &str) -> Self {
+        let mut postal_address = PostalAddress {
+            zip_code: zip_code.trim().to_string(),
+            town: town.trim().to_string(),
+            ..PostalAddress::default()
+        };
+        let mut care_of_lines: Vec<String> = Vec::new();
+        let mut other_lines: Vec<String> = Vec::new();
+        for line in address.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            if is_postbox(line) {
+                postal_address.postbox = line.to_string();
+            } else if is_care_of(line) {
+                care_of_lines.push(line.to_string());
+            } else {
+                other_lines.push(line.to_string());
+            }
+        }
+        // the street is the last line, everything before belongs to the recipient
+        if let Some(street_line) = other_lines.pop() {
+            let (street, house_number, addition) = split_street_line(&street_line);
+            postal_address.street = street;
+            postal_address.house_number = house_number;
+            postal_address.addition = addition;
+        }
+        other_lines.extend(care_of_lines);
+        postal_address.recipient_lines = other_lines;
+        return postal_address;
+    }
+
+    /// builds the address from the separate fields of newer hitobito versions
+    pub fn from_parts(
+        care_of: &str,
+        street: &str,
+        housenumber: &str,
+        postbox: &str,
+        zip_code: &str,
+        town: &str,
+    ) -> Self {
+        let (house_number, addition) = split_house_number(housenumber.trim());
+        PostalAddress {
+            recipient_lines: care_of
+                .lines()
+                .map(str::trim)
+                .filter(|l| !l.is_empty())
+                .map(String::from)
+                .collect(),
+            street: street.trim().to_string(),
+            house_number: house_number,
+            addition: addition,
+            postbox: postbox.trim().to_string(),
+            zip_code: zip_code.trim().to_string(),
+            town: town.trim().to_string(),
+            country: None,
+        }
+    }
+
+    /// sets the country as delivered by the database. Empty means unknown.
+    pub fn with_country(mut self, country: &str) -> Self {
+        let country = country.trim();
+        self.country = if country.is_empty() {
+            None
+        } else {
+            Some(country.to_string())
+        };
+        self
+    }
+
+    /// street, house number and addition as one line, e.g. "Hauptstrasse 5a"
+    pub fn street_line(&self) -> String {
+        let mut line = self.street.clone();
+        if !self.house_number.is_empty() {
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&self.house_number);
+        }
+        if !self.addition.is_empty() {
+            // "5a", "12bis" and "3/5", but "5 Whg 3"
+            let short_letters = self.addition.chars().count() <= 3
+                && self.addition.chars().all(char::is_alphabetic);
+            let starts_with_separator = !self.addition.starts_with(char::is_alphanumeric);
+            if !short_letters && !starts_with_separator && !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&self.addition);
+        }
+        return line;
+    }
+
+    /// whether a couvert can be delivered to this address at all
+    pub fn is_complete(&self) -> bool {
+        (!self.street.is_empty() || !self.postbox.is_empty())
+            && !self.zip_code.is_empty()
+            && !self.town.is_empty()
+    }
+
+    /// Whether both addresses point to the same household, even if they are spelled differently.
+    /// An addition only makes a difference if both addresses have one: "Hauptstr 5" and
+    /// "Hauptstrasse 5a" are the same household, "Hauptstrasse 5a" and "Hauptstrasse 5b" are not.
+    ///
+    /// The country is not compared, because the database spells it as code or as name in several
+    /// languages. combine compares it with its table of countries.
+    pub fn same_household(&self, other: &PostalAddress) -> bool {
+        let additions_conflict = !self.addition.is_empty()
+            && !other.addition.is_empty()
+            && normalize_part(&self.addition) != normalize_part(&other.addition);
+        self.zip_code == other.zip_code
+            && normalize_part(&self.town) == normalize_part(&other.town)
+            && normalize_street(&self.street) == normalize_street(&other.street)
+            && self.house_number == other.house_number
+            && normalize_part(&self.postbox) == normalize_part(&other.postbox)
+            && !additions_conflict
+    }
+
+    /// the street in a form that is equal for different spellings, for sorting and comparing
+    pub fn street_key(&self) -> String {
+        normalize_street(&self.street)
+    }
+}
+
+fn is_postbox(line: &str) -> bool {
+    let lower = line.to_lowercase();
+    POSTBOX_PREFIXES
+        .iter()
+        .any(|prefix| lower.starts_with(prefix))
+}
+
+fn is_care_of(line: &str) -> bool {
+    let lower = line.to_lowercase();
+    CARE_OF_PREFIXES
+        .iter()
+        .any(|prefix| lower.starts_with(prefix))
+}
+
+/// "Hauptstrasse 5a" -> ("Hauptstrasse", "5", "a")
+///
+/// The house number is the last word that starts with a digit, as long as at most one short word
+/// follows it. That way "Via 4 Novembre" stays a street without a number.
+fn split_street_line(line: &str) -> (String, String, String) {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let number_position = words
+        .iter()
+        .rposition(|word| word.starts_with(|c: char| c.is_ascii_digit()));
+    match number_position {
+        Some(position)
+            if position > 0
+                && (position + 1 == words.len()
+                    || (position + 2 == words.len()
+                        && words[position + 1].chars().count() <= 3)) =>
+        {
+            let (house_number, mut addition) = split_house_number(words[position]);
+            if let Some(rest) = words.get(position + 1) {
+                if !addition.is_empty() {
+                    addition.push(' ');
+                }
+                addition.push_str(rest);
+            }
+            (words[..position].join(" "), house_number, addition)
+        },
+        _ => (words.join(" "), String::new(), String::new()),
+    }
+}
+
+/// "5a" -> ("5", "a"), "12 bis" -> ("12", "bis")
+fn split_house_number(housenumber: &str) -> (String, String) {
+    let digits_end = housenumber
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(housenumber.len());
+    let (number, addition) = housenumber.split_at(digits_end);
+    (number.to_string(), addition.trim().to_string())
+}
+
+fn normalize_part(part: &str) -> String {
+    part.split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// lower case, "ß" as "ss", and "str." or "str" at the end of a word as "strasse"
+fn normalize_street(street: &str) -> String {
+    normalize_part(&street.replace("ß", "ss"))
+        .split(' ')
+        .map(|word| {
+            let word = word.trim_end_matches('.');
+            if word.ends_with("str") {
+                format!("{}asse", word)
+            } else {
+                word.to_string()
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PostalAddress;
+
+    #[test]
+    fn test_parse_street_variants() {
+        let cases = vec![
+            ("Hauptstrasse 5", "Hauptstrasse", "5", ""),
+            ("Hauptstr 5a", "Hauptstr", "5", "a"),
+            ("Chemin des Lilas 12 bis", "Chemin des Lilas", "12", "bis"),
+            ("Im Moos 3/5", "Im Moos", "3", "/5"),
+            ("Via 4 Novembre", "Via 4 Novembre", "", ""),
+            ("Dorfplatz", "Dorfplatz", "", ""),
+        ];
+        for (line, street, number, addition) in cases {
+            let address = PostalAddress::parse(line, "8330", "Pfäffikon ZH");
+            assert_eq!(address.street, street, "{}", line);
+            assert_eq!(address.house_number, number, "{}", line);
+            assert_eq!(address.addition, addition, "{}", line);
+        }
+    }
+
+    #[test]
+    fn test_street_line_roundtrip() {
+        for line in vec![
+            "Hauptstrasse 5",
+            "Hauptstrasse 5a",
+            "Im Moos 3/5",
+            "Dorfplatz",
+        ] {
+            assert_eq!(PostalAddress::parse(line, "", "").street_line(), line);
+        }
+    }
+
+    #[test]
+    fn test_postbox_only() {
+        let address = PostalAddress::parse("Case postale 44", "1700", "Fribourg");
+        assert_eq!(address.street, "");
+        assert_eq!(address.postbox, "Case postale 44");
+        assert!(address.is_complete());
+    }
+
+    #[test]
+    fn test_care_of_is_never_the_street() {
+        for lines in vec!["c/o Beispiel\nPostfach 12", "Postfach 12\nc/o Beispiel"] {
+            let address = PostalAddress::parse(lines, "8330", "Pfäffikon ZH");
+            assert_eq!(address.recipient_lines, vec!["c/o Beispiel"], "{}", lines);
+            assert_eq!(address.street, "", "{}", lines);
+            assert_eq!(address.postbox, "Postfach 12", "{}", lines);
+        }
+        let address =
+            PostalAddress::parse("Hauptstrasse 5\nc/o Beispiel", "8330", "Pfäffikon ZH");
+        assert_eq!(address.recipient_lines, vec!["c/o Beispiel"]);
+        assert_eq!(address.street, "Hauptstrasse");
+    }
+
+    #[test]
+    fn test_same_household_despite_spelling() {
+        let a = PostalAddress::parse("Hauptstrasse 5", "8330", "Pfäffikon ZH");
+        let b = PostalAddress::parse("Hauptstr 5a", "8330", "Pfäffikon ZH");
+        let c = PostalAddress::parse("Hauptstr. 5b", "8330", "Pfäffikon ZH");
+        let d = PostalAddress::parse("Hauptstrasse 7", "8330", "Pfäffikon ZH");
+        assert!(a.same_household(&b));
+        assert!(a.same_household(&c));
+        assert!(!b.same_household(&c));
+        assert!(!a.same_household(&d));
+    }
+
+    #[test]
+    fn test_from_parts() {
+        let address = PostalAddress::from_parts(
+            "c/o Muster",
+            "Hauptstrasse",
+            "5a",
+            "",
+            "8330",
+            "Pfäffikon ZH",
+        );
+        assert_eq!(address.recipient_lines, vec!["c/o Muster"]);
+        assert_eq!(address.house_number, "5");
+        assert_eq!(address.addition, "a");
+        assert_eq!(
+            address,
+            PostalAddress::parse("c/o Muster\nHauptstrasse 5a", "8330", "Pfäffikon ZH")
+        );
+    }
+}
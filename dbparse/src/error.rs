@@ -0,0 +1,162 @@
+//! Everything that can go wrong while getting the people out of the database.
+//!
+//! The messages are meant to be shown to the user as they are, so they are in German and say what
+//! to do about the problem.
+use std::fmt;
+
+/// endpoints are identified by their position in config.yaml (starting at 1) instead of their link,
+/// because the link contains the token.
+#[derive(Debug)]
+pub enum Error {
+    /// config.yaml did not exist. A template has been generated in its place.
+    MissingConfig { filename: String },
+    /// config.yaml exists, but could not be read or understood
+    Config { filename: String, reason: String },
+    /// config.yaml does not contain a single endpoint
+    NoEndpoints,
+    /// the database could not be reached at all
+    Network { endpoint_number: usize, reason: String },
+    /// the database answered, but not with success
+    Http { endpoint_number: usize, status: u16 },
+    /// the database refused the credentials
+    Auth { endpoint_number: usize },
+    /// the database answered with something we do not understand
+    Parse { endpoint_number: usize, reason: String },
+    /// all endpoints together did not deliver a single person
+    EmptyDataset,
+    /// a person refers to a role that is not contained in the response
+    DanglingRoleRef { person_id: usize, role_id: String },
+    /// a role refers to a group that is not contained in the response
+    DanglingGroupRef { role_id: String, group_id: String },
+    /// a snapshot file could not be read or written
+    Snapshot { path: String, reason: String },
+    /// mapping.yaml could not be read or written
+    Mapping { reason: String },
+    /// the next-page links of an endpoint led back to a page already fetched, or did not end after
+    /// `pages` pages. The people on the missing pages would be lost.
+    Pagination { endpoint_number: usize, pages: usize },
+}
+
+impl Error {
+    /// the code the process should exit with, so that scripts can tell the problems apart
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::MissingConfig { .. } => 2,
+            Error::Config { .. } => 3,
+            Error::NoEndpoints => 3,
+            Error::Network { .. } => 4,
+            Error::Http { .. } => 5,
+            Error::Auth { .. } => 6,
+            Error::Parse { .. } => 7,
+            Error::EmptyDataset => 8,
+            Error::DanglingRoleRef { .. } => 9,
+            Error::DanglingGroupRef { .. } => 9,
+            Error::Snapshot { .. } => 10,
+            Error::Mapping { .. } => 11,
+            Error::Pagination { .. } => 12,
+        }
+    }
+
+    pub(crate) fn parse(endpoint_number: usize, error: &dyn std::error::Error) -> Self {
+        Error::Parse {
+            endpoint_number: endpoint_number,
+            reason: error.to_string(),
+        }
+    }
+
+    pub(crate) fn snapshot(path: &str, error: &dyn std::error::Error) -> Self {
+        Error::Snapshot {
+            path: path.to_string(),
+            reason: error.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::MissingConfig { filename } => write!(
+                f,
+                "Die Datei {} fehlte. Es wurde eine Vorlage erstellt. Trage dort die Tokens und die Endpoints ein und versuche es dann nochmals.",
+                filename
+            ),
+            Error::Config { filename, reason } if filename == crate::CONFIG_YAML_FILE => write!(
+                f,
+                "Die Datei {} konnte nicht gelesen werden ({}). Ist sie korrekt yaml-formatiert und enthält sie den Abschnitt `db_conf` mit `api_token`, `login_email`, `service_token` und `versand_endpoint_fmtstrs`?",
+                filename, reason
+            ),
+            Error::Config { filename, reason } => write!(
+                f,
+                "Die Datei {} konnte nicht verwendet werden: {}",
+                filename, reason
+            ),
+            Error::NoEndpoints => write!(
+                f,
+                "In config.yaml ist unter `versand_endpoint_fmtstrs` kein einziger Endpoint eingetragen. Füge mindestens einen Link zu den Personen einer Gruppe hinzu."
+            ),
+            Error::Network {
+                endpoint_number,
+                reason,
+            } => write!(
+                f,
+                "Endpoint {} konnte nicht erreicht werden ({}). Besteht eine Internetverbindung? Ist db.cevi.ch erreichbar?",
+                endpoint_number, reason
+            ),
+            Error::Http {
+                endpoint_number,
+                status,
+            } => write!(
+                f,
+                "Endpoint {} antwortete mit HTTP-Status {}. Stimmen die Gruppen-id und die filter_id im Link in config.yaml?",
+                endpoint_number, status
+            ),
+            Error::Auth { endpoint_number } => write!(
+                f,
+                "Endpoint {} verweigerte den Zugriff. Ist das Token in config.yaml noch gültig, und hat es die Berechtigung \"Personen von Untergruppen lesen\"? Beinhaltet der Link `token={{service_token}}` bzw. `user_email={{login_email}}&user_token={{api_token}}`?",
+                endpoint_number
+            ),
+            Error::Parse {
+                endpoint_number,
+                reason,
+            } => write!(
+                f,
+                "Die Antwort von Endpoint {} konnte nicht gelesen werden ({}). Zeigt der Link in config.yaml wirklich auf eine Personenliste, z.B. `.../groups/2423/people.json?token={{service_token}}`?",
+                endpoint_number, reason
+            ),
+            Error::EmptyDataset => write!(
+                f,
+                "Die Datenbank hat keine einzige Person geliefert. Sind die Gruppen-ids und filter_ids in den Endpoints korrekt, und sieht das Token die Personen dieser Gruppen?"
+            ),
+            Error::DanglingRoleRef { person_id, role_id } => write!(
+                f,
+                "Person {} hat die Rolle {}, die in der Antwort der Datenbank fehlt. Hat das Token genügend Rechte? Bei JSON:API-Endpoints muss der Link `include=roles` enthalten.",
+                person_id, role_id
+            ),
+            Error::DanglingGroupRef { role_id, group_id } => write!(
+                f,
+                "Die Rolle {} gehört zur Gruppe {}, die in der Antwort der Datenbank fehlt. Hat das Token genügend Rechte? Bei JSON:API-Endpoints müssen die Gruppen der Rollen mit `include=...` mitgeliefert werden.",
+                role_id, group_id
+            ),
+            Error::Snapshot { path, reason } => write!(
+                f,
+                "Der Snapshot {} konnte nicht verwendet werden ({}). Existiert die Datei, und wurde sie mit `fetch --save-snapshot` erstellt?",
+                path, reason
+            ),
+            Error::Mapping { reason } => write!(
+                f,
+                "Die Datei mapping.yaml konnte nicht verarbeitet werden ({}). Lösche oder verschiebe sie, dann wird sie beim nächsten Durchlauf neu generiert.",
+                reason
+            ),
+            Error::Pagination {
+                endpoint_number,
+                pages,
+            } => write!(
+                f,
+                "Endpoint {} hörte nach {} Seiten nicht auf, auf weitere Seiten zu verweisen, oder verwies auf eine Seite, die schon geholt wurde. Damit keine Personen fehlen, wurde abgebrochen. Stimmt der Link in config.yaml?",
+                endpoint_number, pages
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
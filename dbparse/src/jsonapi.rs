@@ -0,0 +1,446 @@
+//! Parser for the current hitobito JSON:API format, as served e.g. by
+//! `https://db.cevi.ch/api/people?include=roles`
+//!
+//! The response looks roughly like this:
+//!
+//! ```json
+//! {
+//!   "data": [
+//!     { "id": "6468", "type": "people",
+//!       "attributes": { "first_name": "Eric", "last_name": "Mink", "nickname": "...",
+//!                       "street": "...", "housenumber": "...", "zip_code": "...", "town": "..." },
+//!       "relationships": { "roles": { "data": [ { "id": "37855", "type": "roles" } ] } } }
+//!   ],
+//!   "included": [
+//!     { "id": "37855", "type": "roles",
+//!       "attributes": { "type": "Group::Stufe::Teilnehmer", "label": null, "group_id": 1341 },
+//!       "relationships": { "group": { "data": { "id": "1341", "type": "groups" } } } },
+//!     { "id": "1341", "type": "groups",
+//!       "attributes": { "name": "Senshi (M)", "type": "Group::Stufe", "layer_group_id": 115 } }
+//!   ]
+//! }
+//! ```
+//!
+//! It is turned into the very same [ReasonableDataset](../struct.ReasonableDataset.html) as the
+//! legacy format, so that nothing after dbparse needs to know which format was used.
+use super::{
+    Error, Group, PostalAddress, ReasonableDataset, ReasonableGroup, ReasonablePerson, Role,
+};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+const TYPE_PEOPLE: &str = "people";
+const TYPE_ROLES: &str = "roles";
+const TYPE_GROUPS: &str = "groups";
+
+/// the top level of a JSON:API response
+#[derive(Deserialize, Debug)]
+struct Document {
+    data: Vec<Resource>,
+    #[serde(default)]
+    included: Vec<Resource>,
+}
+
+/// a single entry in `data` or `included`. The attributes differ depending on `resource_type`, so
+/// they are only deserialized once we know what we are looking at.
+#[derive(Deserialize, Debug)]
+struct Resource {
+    #[serde(deserialize_with = "serde_aux::field_attributes::deserialize_string_from_number")]
+    id: String,
+    #[serde(rename = "type")]
+    resource_type: String,
+    #[serde(default)]
+    attributes: serde_json::Value,
+    #[serde(default)]
+    relationships: HashMap<String, Relationship>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Relationship {
+    #[serde(default)]
+    data: Option<RelationshipData>,
+}
+
+/// a relationship links either to one or to many other resources
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum RelationshipData {
+    One(ResourceIdentifier),
+    Many(Vec<ResourceIdentifier>),
+}
+
+#[derive(Deserialize, Debug)]
+struct ResourceIdentifier {
+    #[serde(deserialize_with = "serde_aux::field_attributes::deserialize_string_from_number")]
+    id: String,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+struct PersonAttributes {
+    #[serde(with = "super::null_str_serder")]
+    first_name: String,
+    #[serde(with = "super::null_str_serder")]
+    last_name: String,
+    #[serde(with = "super::null_str_serder")]
+    nickname: String,
+    /// older hitobito versions store the whole street line in here
+    #[serde(with = "super::null_str_serder")]
+    address: String,
+    #[serde(with = "super::null_str_serder")]
+    street: String,
+    #[serde(with = "super::null_str_serder")]
+    housenumber: String,
+    /// e.g. "c/o Muster"
+    #[serde(with = "super::null_str_serder")]
+    address_care_of: String,
+    #[serde(with = "super::null_str_serder")]
+    postbox: String,
+    #[serde(with = "super::null_str_serder")]
+    zip_code: String,
+    #[serde(with = "super::null_str_serder")]
+    town: String,
+    #[serde(with = "super::null_str_serder")]
+    country: String,
+    #[serde(with = "super::null_str_serder")]
+    salutation_parents: String,
+    #[serde(with = "super::null_str_serder")]
+    name_parents: String,
+    #[serde(with = "super::null_str_serder")]
+    birthday: String,
+}
+
+/// `role_type` is the ruby class name, e.g. "Group::Stufe::Teilnehmer". Some endpoints also
+/// deliver the human readable `name`, which is what the legacy format called `role_type`.
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+struct RoleAttributes {
+    #[serde(rename = "type", with = "super::null_str_serder")]
+    role_type: String,
+    #[serde(with = "super::null_str_serder")]
+    name: String,
+    label: Option<String>,
+    #[serde(deserialize_with = "optional_id")]
+    group_id: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+struct GroupAttributes {
+    #[serde(with = "super::null_str_serder")]
+    name: String,
+    #[serde(rename = "type", with = "super::null_str_serder")]
+    group_type: String,
+    #[serde(deserialize_with = "optional_id")]
+    layer_group_id: Option<String>,
+}
+
+/// ids are sometimes numbers and sometimes strings, and sometimes null
+fn optional_id<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let value = Option::<serde_json::Value>::deserialize(deserializer)?;
+    Ok(match value {
+        Some(serde_json::Value::String(s)) => Some(s),
+        Some(serde_json::Value::Number(n)) => Some(n.to_string()),
+        _ => None,
+    })
+}
+
+/// "Group::Stufe::Teilnehmer" => "Teilnehmer"
+fn last_class_segment(class_name: &str) -> String {
+    class_name
+        .rsplit("::")
+        .next()
+        .unwrap_or(class_name)
+        .to_string()
+}
+
+/// The ruby class names of the roles and what the legacy format called them. roles.yaml is keyed by
+/// the legacy names, so that a person gets the same badge whichever format the database answered
+/// in. Classes that are not listed keep their last segment.
+const LEGACY_ROLE_TYPES: &[(&str, &str)] = &[
+    ("Teilnehmer", "Teilnehmer/-in"),
+    ("Minigruppenleiter", "Minigruppenleiter/-in"),
+    ("Gruppenleiter", "Gruppenleiter/-in"),
+    ("Stufenleiter", "Stufenleiter/-in"),
+    ("Abteilungsleiter", "Abteilungsleiter/-in"),
+    ("Froeschlileiter", "Fröschlileiter/-in"),
+    ("Froeschlihauptleiter", "Fröschlihauptleiter/-in"),
+    ("Adressverwalter", "Adressverwalter/-in"),
+    ("Chorsaenger", "Chorsänger/-in"),
+    ("FreierMitarbeiter", "Freie/-r Mitarbeiter/-in"),
+    ("Hausverantwortlicher", "Hausverantwortliche/-r"),
+    ("AdministratorCeviDB", "Administrator/-in Cevi DB"),
+    ("Externer", "Externe/-r"),
+    ("Laedeliverantwortlicher", "Lädeliverantwortliche/-r"),
+    ("Aktuar", "Aktuar/-in"),
+    ("Materialverantwortlicher", "Materialverantwortliche/-r"),
+    ("Verantwortlicher", "Verantwortliche/-r"),
+];
+
+/// "Group::Stufe::Teilnehmer" => "Teilnehmer/-in"
+fn legacy_role_type(class_name: &str) -> String {
+    let segment = last_class_segment(class_name);
+    match LEGACY_ROLE_TYPES
+        .iter()
+        .find(|(class, _)| *class == segment)
+    {
+        Some((_, legacy)) => legacy.to_string(),
+        None => segment,
+    }
+}
+
+/// The ruby class names of the group types whose legacy names differ. `group_types` in
+/// group_priorities.yaml and the group conditions in roles.yaml are keyed by the legacy names.
+const LEGACY_GROUP_TYPES: &[(&str, &str)] = &[("TenSing", "Ten-Sing"), ("Froeschli", "Fröschli")];
+
+/// "Group::TenSing" => "Ten-Sing"
+fn legacy_group_type(class_name: &str) -> String {
+    let segment = last_class_segment(class_name);
+    match LEGACY_GROUP_TYPES
+        .iter()
+        .find(|(class, _)| *class == segment)
+    {
+        Some((_, legacy)) => legacy.to_string(),
+        None => segment,
+    }
+}
+
+/// true if the body looks like a JSON:API document rather than the legacy `people`/`linked` shape
+pub fn is_json_api(body: &str) -> bool {
+    match serde_json::from_str::<serde_json::Value>(body) {
+        Ok(value) => value.get("data").is_some() && value.get("people").is_none(),
+        Err(_) => false,
+    }
+}
+
+pub fn to_reasonable_dataset(
+    body: &str,
+    endpoint_number: usize,
+) -> Result<ReasonableDataset, Error> {
+    let parse_error = |e: serde_json::Error| Error::parse(endpoint_number, &e);
+    let document: Document = serde_json::from_str(body).map_err(parse_error)?;
+
+    // index everything that was included, so that relationships can be resolved
+    let mut groups: HashMap<String, Group> = HashMap::new();
+    let mut layer_groups: HashMap<String, String> = HashMap::new();
+    let mut roles: HashMap<String, Role> = HashMap::new();
+    for resource in document.included.iter() {
+        if resource.resource_type == TYPE_GROUPS {
+            let attributes: GroupAttributes =
+                serde_json::from_value(resource.attributes.clone()).map_err(parse_error)?;
+            if let Some(layer_group_id) = attributes.layer_group_id {
+                layer_groups.insert(resource.id.clone(), layer_group_id);
+            }
+            groups.insert(
+                resource.id.clone(),
+                Group {
+                    id: resource.id.clone(),
+                    name: attributes.name.trim().to_string(),
+                    group_type: legacy_group_type(&attributes.group_type),
+                },
+            );
+        }
+    }
+    for resource in document.included.iter() {
+        if resource.resource_type == TYPE_ROLES {
+            let attributes: RoleAttributes =
+                serde_json::from_value(resource.attributes.clone()).map_err(parse_error)?;
+            let group_id = attributes
+                .group_id
+                .clone()
+                .or_else(|| resource.single_relationship("group"))
+                .unwrap_or_default();
+            let layer_group = layer_groups.get(&group_id).cloned().unwrap_or_default();
+            let role_type = if attributes.name.trim().is_empty() {
+                legacy_role_type(&attributes.role_type)
+            } else {
+                attributes.name.trim().to_string()
+            };
+            roles.insert(
+                resource.id.clone(),
+                Role::new(
+                    Rc::from(&*resource.id),
+                    role_type,
+                    attributes.label.clone(),
+                    group_id,
+                    layer_group,
+                ),
+            );
+        }
+    }
+
+    let mut all_groups: HashSet<ReasonableGroup> = HashSet::new();
+    let mut all_people: Vec<ReasonablePerson> = Vec::new();
+    for resource in document.data.iter() {
+        if resource.resource_type != TYPE_PEOPLE {
+            continue;
+        }
+        let attributes: PersonAttributes =
+            serde_json::from_value(resource.attributes.clone()).map_err(parse_error)?;
+        let address = if attributes.address.trim().is_empty() {
+            PostalAddress::from_parts(
+                &attributes.address_care_of,
+                &attributes.street,
+                &attributes.housenumber,
+                &attributes.postbox,
+                &attributes.zip_code,
+                &attributes.town,
+            )
+        } else {
+            PostalAddress::parse(&attributes.address, &attributes.zip_code, &attributes.town)
+        }
+        .with_country(&attributes.country);
+
+        let mut reasonable_person = ReasonablePerson {
+            id: resource
+                .id
+                .parse::<usize>()
+                .map_err(|e| Error::parse(endpoint_number, &e))?,
+            first_name: attributes.first_name.trim().to_string(),
+            last_name: attributes.last_name.trim().to_string(),
+            nickname: attributes.nickname.trim().to_string(),
+            address: address,
+            salutation_parents: attributes.salutation_parents.trim().to_string(),
+            name_parents: attributes.name_parents.trim().to_string(),
+            birthday: attributes.birthday.trim().to_string(),
+            roles: HashSet::<Role>::new(),
+            groups: HashSet::<ReasonableGroup>::new(),
+        };
+
+        for role_id in resource.many_relationship("roles") {
+            let role: &Role = roles.get(&role_id).ok_or(Error::DanglingRoleRef {
+                person_id: reasonable_person.id,
+                role_id: role_id.clone(),
+            })?;
+            reasonable_person.roles.insert(role.clone());
+
+            let group: Group = groups
+                .get(&role.links.group_id)
+                .ok_or(Error::DanglingGroupRef {
+                    role_id: role_id.clone(),
+                    group_id: role.links.group_id.clone(),
+                })?
+                .clone();
+            let reasonable_group: ReasonableGroup = group.into();
+            reasonable_person.groups.insert(reasonable_group.clone());
+            all_groups.insert(reasonable_group);
+        }
+
+        all_people.push(reasonable_person);
+    }
+
+    Ok(ReasonableDataset {
+        people: all_people,
+        groups: all_groups,
+    })
+}
+
+impl Resource {
+    fn single_relationship(&self, name: &str) -> Option<String> {
+        match self.relationships.get(name).and_then(|r| r.data.as_ref()) {
+            Some(RelationshipData::One(identifier)) => Some(identifier.id.clone()),
+            _ => None,
+        }
+    }
+
+    fn many_relationship(&self, name: &str) -> Vec<String> {
+        match self.relationships.get(name).and_then(|r| r.data.as_ref()) {
+            Some(RelationshipData::Many(identifiers)) => {
+                identifiers.iter().map(|i| i.id.clone()).collect()
+            },
+            Some(RelationshipData::One(identifier)) => vec![identifier.id.clone()],
+            None => Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    const SAMPLE: &str = r###"{
+        "data": [
+            { "id": "6468", "type": "people",
+              "attributes": { "first_name": "Eric ", "last_name": "Mink", "nickname": "Levanzo",
+                              "street": "Musterstrasse", "housenumber": "2", "zip_code": "8330",
+                              "town": "Pfäffikon ZH", "country": "CH", "name_parents": null,
+                              "birthday": "2008-04-01" },
+              "relationships": { "roles": { "data": [ { "id": "37855", "type": "roles" } ] } } }
+        ],
+        "included": [
+            { "id": "37855", "type": "roles",
+              "attributes": { "type": "Group::Stufe::Stufenleiter", "label": null,
+                              "group_id": 1341 } },
+            { "id": 1341, "type": "groups",
+              "attributes": { "name": "Senshi (M)", "type": "Group::Stufe",
+                              "layer_group_id": 115 } }
+        ],
+        "links": { "next": null }
+    }"###;
+
+    #[test]
+    fn test_legacy_role_type() {
+        assert_eq!(
+            super::legacy_role_type("Group::Stufe::Teilnehmer"),
+            "Teilnehmer/-in"
+        );
+        assert_eq!(
+            super::legacy_role_type("Group::Externe::Externer"),
+            "Externe/-r"
+        );
+        assert_eq!(super::legacy_role_type("Group::Abteilung::Coach"), "Coach");
+        assert_eq!(
+            super::legacy_role_type("Group::Neu::Unbekannt"),
+            "Unbekannt"
+        );
+    }
+
+    #[test]
+    fn test_legacy_group_type() {
+        let ten_sing = SAMPLE.replace(r#""type": "Group::Stufe","#, r#""type": "Group::TenSing","#);
+        let dataset = super::to_reasonable_dataset(&ten_sing, 1).expect("should parse");
+        let group = dataset.groups.iter().next().expect("should have a group");
+        assert_eq!(group.inner_group.group_type, "Ten-Sing");
+        assert_eq!(super::legacy_group_type("Group::Froeschli"), "Fröschli");
+        assert_eq!(super::legacy_group_type("Group::Stufe"), "Stufe");
+    }
+
+    #[test]
+    fn test_detects_json_api() {
+        assert!(super::is_json_api(SAMPLE));
+        assert!(!super::is_json_api(r#"{"people": [], "linked": {}}"#));
+    }
+
+    #[test]
+    fn test_missing_group_is_an_error() {
+        let without_groups = SAMPLE.replace(r#""type": "groups""#, r#""type": "phone_numbers""#);
+        match super::to_reasonable_dataset(&without_groups, 1) {
+            Err(super::Error::DanglingGroupRef { group_id, .. }) => assert_eq!(group_id, "1341"),
+            other => panic!("expected a dangling group, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_json_api_to_reasonable_dataset() {
+        let dataset = super::to_reasonable_dataset(SAMPLE, 1).expect("should parse");
+        assert_eq!(dataset.people.len(), 1);
+        let person = &dataset.people[0];
+        assert_eq!(person.id, 6468);
+        assert_eq!(person.first_name, "Eric");
+        assert_eq!(person.address.street, "Musterstrasse");
+        assert_eq!(person.address.house_number, "2");
+        assert_eq!(person.address.zip_code, "8330");
+        assert_eq!(person.address.country, Some(String::from("CH")));
+        assert_eq!(person.name_parents, "");
+        assert_eq!(person.salutation_parents, "");
+        assert_eq!(person.birthday, "2008-04-01");
+        let role = person.roles.iter().next().expect("should have a role");
+        assert_eq!(role.role_type, "Stufenleiter/-in");
+        let group = person.groups.iter().next().expect("should have a group");
+        assert_eq!(group.inner_group.name, "Senshi (M)");
+        assert_eq!(group.inner_group.group_type, "Stufe");
+        assert_eq!(dataset.groups.len(), 1);
+    }
+}
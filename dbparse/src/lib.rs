@@ -1,15 +1,23 @@
-// TODO: error messages e.g. when no internet
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::collections::HashSet;
-use std::error::Error;
 use std::fs;
 use std::fs::read_to_string;
 use std::fs::File;
 use std::io::Write;
 use std::rc::Rc;
+pub mod address;
+pub mod error;
+mod jsonapi;
 pub mod mapping;
+mod pagination;
+pub mod snapshot;
+pub mod telemetry;
+pub use address::PostalAddress;
+pub use error::Error;
 use mapping::GroupMapping;
+use snapshot::{Snapshot, SnapshotPage};
+use telemetry::TelemetryConf;
 
 // config.yaml is stored both in examples dir and in dbparse dir, currently. Because it is read
 // from the working dir
@@ -49,13 +57,42 @@ const CONFIG_YAML_FILLABLE_TEMPLATE: &str = r###"db_conf:
     # Bei login-type USERTOKEN sind die links generell von der Form
     #    https://db.cevi.ch/groups/2423/people.json?user_email=[login_email]&user_token=[api_token]
     # nur mit geschweiften Klammern {} statt eckigen Klammern [].
+    # Das Format der Antwort wird automatisch erkannt. Falls nötig, kann es pro endpoint mit
+    # `format: legacy` oder `format: json_api` festgelegt werden, wie im dritten Beispiel.
     versand_endpoint_fmtstrs:
         - "https://db.cevi.ch/groups/2423/people.json?token={service_token}"
         - "https://db.cevi.ch/groups/116/people.json?filter_id=319&user_email={login_email}&user_token={api_token}"
+        #- url: "https://db.cevi.ch/api/people?include=roles&token={service_token}"
+        #  format: json_api
+telemetry:
+    # Falls eingeschaltet, wird bei jedem `run` eine einzelne Anfrage an die url geschickt, damit
+    # gezählt werden kann, wie oft das Programm verwendet wird. Es werden keine Daten mitgeschickt.
+    # Mit `run --no-telemetry` wird es auch dann nicht gemacht.
+    enabled: false
+    url: "https://eric.mink.li/src/php/ccount/click.php?id=cevi_versand_usage"
+# Das Format der Couverts: C4, C5, C6, C6/5, DL, oder custom mit width und height in mm.
+# Mit address_x und address_y (in mm von unten links) kann die Adresse für jedes Format an ein
+# anderes Fenster angepasst werden. Ohne diesen Abschnitt wird C5 verwendet. `run --envelope DL`
+# hat Vorrang.
+#envelope:
+#    format: C6/5
+#    address_y: 50
+# Ein eigener Etikettenbogen für `run --labels config`, alle Masse in mm von oben links.
+# pitch_x und pitch_y sind die Abstände von Etikette zu Etikette, falls zwischen den Etiketten
+# Platz ist.
+#labels:
+#    rows: 8
+#    columns: 3
+#    label_width: 70
+#    label_height: 36
+#    margin_top: 4.5
+#    margin_left: 0
 "###;
 
+/// safety net in case the database keeps linking to further pages forever
+const MAX_PAGES_PER_ENDPOINT: usize = 1000;
+
 const SIGNIN_POST_URL: &str = "https://db.cevi.ch/users/sign_in.json";
-const USAGE_TRACKING_URL: &str = "https://eric.mink.li/src/php/ccount/click.php?id=cevi_versand_usage";
 
 pub enum Verbosity {
     No,
@@ -78,26 +115,86 @@ pub struct MainReturns {
     pub dataset: ReasonableDataset,
 }
 
+/// Errors are reported to `user_interface` before they are returned.
+///
+/// Usage is only tracked if `telemetry_allowed` is set and it is also enabled in config.yaml
 #[cfg(not(target_arch = "wasm32"))]
-pub fn run(user_interface: &dyn DbparseInteractor) -> Result<MainReturns, Box<dyn Error>> {
+pub fn run(
+    user_interface: &dyn DbparseInteractor,
+    telemetry_allowed: bool,
+) -> Result<MainReturns, Error> {
+    report_error(user_interface, run_inner(user_interface, telemetry_allowed))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn run_inner(
+    user_interface: &dyn DbparseInteractor,
+    telemetry_allowed: bool,
+) -> Result<MainReturns, Error> {
     // load database API token
-    let config = setup_config(user_interface);
-    let _failure = track_usage();
-    let dataset: ReasonableDataset = get_data_for_versand(&config).expect("WTF in main! Perhaps the credentials or the endpoint url are invalid?");
+    let config = setup_config(user_interface)?;
+    let telemetry_conf: TelemetryConf = read_config_section("telemetry")?.unwrap_or_default();
+    if telemetry_allowed && telemetry_conf.enabled {
+        let result = telemetry::track_usage(&telemetry_conf);
+        user_interface.on_telemetry_finished(result.as_ref().map(|_| ()).map_err(|e| &**e));
+    }
+    let dataset: ReasonableDataset = get_data_for_versand(&config, user_interface)?;
     user_interface.on_download_finished();
     return run_with_reasonable_dataset(dataset);
 }
 
-pub fn run_with_reasonable_dataset(
-    dataset: ReasonableDataset,
-) -> Result<MainReturns, Box<dyn Error>> {
+/// like `run`, but reads the database responses from a snapshot file created by `fetch_snapshot`
+/// instead of downloading them.
+pub fn run_from_snapshot(
+    user_interface: &dyn DbparseInteractor,
+    snapshot_path: &str,
+) -> Result<MainReturns, Error> {
+    let result = Snapshot::load(snapshot_path)
+        .and_then(|snapshot| reasonablify_pages(&snapshot.pages))
+        .and_then(|dataset| {
+            user_interface.on_download_finished();
+            run_with_reasonable_dataset(dataset)
+        });
+    report_error(user_interface, result)
+}
+
+/// downloads the raw responses of all endpoints and stores them in `snapshot_path`.
+/// Returns the number of pages stored.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn fetch_snapshot(
+    user_interface: &dyn DbparseInteractor,
+    snapshot_path: &str,
+) -> Result<usize, Error> {
+    let result = setup_config(user_interface)
+        .and_then(|config| get_raw_pages_for_versand(&config, user_interface))
+        .and_then(|pages| {
+            user_interface.on_download_finished();
+            let snapshot = Snapshot { pages: pages };
+            snapshot.save(snapshot_path)?;
+            Ok(snapshot.pages.len())
+        });
+    report_error(user_interface, result)
+}
+
+fn report_error<T>(
+    user_interface: &dyn DbparseInteractor,
+    result: Result<T, Error>,
+) -> Result<T, Error> {
+    if let Err(e) = &result {
+        user_interface.on_error(e);
+    }
+    result
+}
+
+pub fn run_with_reasonable_dataset(dataset: ReasonableDataset) -> Result<MainReturns, Error> {
+    let mapping_error = |e: &dyn std::error::Error| Error::Mapping {
+        reason: e.to_string(),
+    };
     // load yaml mapping from file if exists
     let yaml_group_mapping: Result<String, std::io::Error> = read_to_string(MAPPING_YAML_FILE);
     // combine with new groups from database
     let loaded_group_mapping: GroupMapping = match yaml_group_mapping {
-        Ok(mapping) => {
-            mapping::create_map_from_yaml(&mapping).expect("Creating map from yaml failed")
-        },
+        Ok(mapping) => mapping::create_map_from_yaml(&mapping).map_err(|e| mapping_error(&e))?,
         Err(e) => {
             println!("problem loading yaml mapping: {}.\nRecreating it...", e);
             GroupMapping::new()
@@ -110,22 +207,26 @@ pub fn run_with_reasonable_dataset(
         mapping::store_map_in_map(&loaded_group_mapping, &db_group_mapping);
     // save new mapping to file
     let new_yaml_group_mapping: String = mapping::create_yaml_from_map(&merged_group_mapping)
-        .expect("Generating yaml for group mapping failed");
-    let mut file = File::create(MAPPING_YAML_FILE).expect("Writing mapping failed");
-    let res = file.write_all(new_yaml_group_mapping.as_bytes());
-
-    return match res {
-        Ok(_) => Ok(MainReturns {
-            file: file,
-            group_mapping: merged_group_mapping,
-            dataset: dataset,
-        }),
-        Err(e) => Err(Box::new(e)),
-    };
+        .ok_or(Error::Mapping {
+            reason: String::from("Generating yaml for group mapping failed"),
+        })?;
+    let mut file = File::create(MAPPING_YAML_FILE).map_err(|e| mapping_error(&e))?;
+    file.write_all(new_yaml_group_mapping.as_bytes())
+        .map_err(|e| mapping_error(&e))?;
+
+    return Ok(MainReturns {
+        file: file,
+        group_mapping: merged_group_mapping,
+        dataset: dataset,
+    });
 }
 
-fn setup_config(ui: &dyn DbparseInteractor) -> DB_Conf {
+fn setup_config(ui: &dyn DbparseInteractor) -> Result<DB_Conf, Error> {
     let filename = CONFIG_YAML_FILE;
+    let config_error = |reason: String| Error::Config {
+        filename: filename.to_string(),
+        reason: reason,
+    };
     let fil = match fs::File::open(filename) {
         Ok(f) => f,
         Err(_e) => {
@@ -135,18 +236,44 @@ fn setup_config(ui: &dyn DbparseInteractor) -> DB_Conf {
                 "th1s1sY0ur53rvic370k3n",
             );
             ui.error_missing_config_file(filename.to_string());
-            panic!(
-                "Make sure the {} file is set up correctly, then try again.",
-                filename
-            );
+            return Err(Error::MissingConfig {
+                filename: filename.to_string(),
+            });
         },
     };
-    let yaml: serde_yaml::Value = serde_yaml::from_reader(fil).expect("file should be proper YAML");
+    let yaml: serde_yaml::Value =
+        serde_yaml::from_reader(fil).map_err(|e| config_error(e.to_string()))?;
 
-    let db_conf_in_yaml: &serde_yaml::Value = yaml.get("db_conf").unwrap();
-    let db_conf: DB_Conf = serde_yaml::from_value(db_conf_in_yaml.clone()).unwrap();
-    println!("deserialized = {:?}", db_conf);
-    return db_conf;
+    let db_conf_in_yaml: &serde_yaml::Value = yaml
+        .get("db_conf")
+        .ok_or(config_error(String::from("db_conf fehlt")))?;
+    let db_conf: DB_Conf = serde_yaml::from_value(db_conf_in_yaml.clone())
+        .map_err(|e| config_error(e.to_string()))?;
+    if db_conf.versand_endpoint_fmtstrs.is_empty() {
+        return Err(Error::NoEndpoints);
+    }
+    return Ok(db_conf);
+}
+
+/// reads a top level section of config.yaml, e.g. `telemetry`. Returns `None` if config.yaml has
+/// no such section.
+pub fn read_config_section<T>(key: &str) -> Result<Option<T>, Error>
+where
+    T: serde::de::DeserializeOwned,
+{
+    let config_error = |reason: String| Error::Config {
+        filename: CONFIG_YAML_FILE.to_string(),
+        reason: reason,
+    };
+    let fil = fs::File::open(CONFIG_YAML_FILE).map_err(|e| config_error(e.to_string()))?;
+    let yaml: serde_yaml::Value =
+        serde_yaml::from_reader(fil).map_err(|e| config_error(e.to_string()))?;
+    match yaml.get(key) {
+        None => Ok(None),
+        Some(section) => serde_yaml::from_value(section.clone())
+            .map(Some)
+            .map_err(|e| config_error(format!("{}: {}", key, e))),
+    }
 }
 
 /// specify the text that should be in the file as placeholders until the user edits it
@@ -207,7 +334,8 @@ pub fn get_auth_token(login_email: &str, password: &str) -> Result<String, std::
     let data: String = get_auth_token_url_data(login_email, password);
     let body = chttp::post(SIGNIN_POST_URL, data)?.into_body().text()?;
 
-    let yaml: serde_yaml::Value = serde_yaml::from_str(body.as_ref()).unwrap();
+    let yaml: serde_yaml::Value = serde_yaml::from_str(body.as_ref())
+        .map_err(|e| std::io::Error::new(ErrorKind::InvalidData, e))?;
     let auth_token: &serde_yaml::Value = yaml
         .get("people")
         .ok_or(std::io::Error::new(
@@ -242,9 +370,69 @@ pub fn get_auth_token(login_email: &str, password: &str) -> Result<String, std::
 struct DB_Conf {
     api_token: String,
     login_email: String,
-    versand_endpoint_fmtstrs: Vec<String>,
+    versand_endpoint_fmtstrs: Vec<EndpointConf>,
     service_token: String,
 }
+
+/// An endpoint is either just the link, or the link together with the format of its response
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+enum EndpointConf {
+    Url(String),
+    WithFormat {
+        url: String,
+        #[serde(default)]
+        format: ApiFormat,
+    },
+}
+impl EndpointConf {
+    fn url(&self) -> &str {
+        match self {
+            EndpointConf::Url(url) => url,
+            EndpointConf::WithFormat { url, .. } => url,
+        }
+    }
+
+    fn format(&self) -> ApiFormat {
+        match self {
+            EndpointConf::Url(_) => ApiFormat::Auto,
+            EndpointConf::WithFormat { format, .. } => *format,
+        }
+    }
+}
+
+/// The shape of the json the database responds with.
+///
+/// * `Legacy` is the old `people.json` format with `people` and `linked`
+/// * `JsonApi` is the newer hitobito format with `data`, `included` and `relationships`
+/// * `Auto` looks at the body to decide which one it is
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ApiFormat {
+    Auto,
+    Legacy,
+    JsonApi,
+}
+impl Default for ApiFormat {
+    fn default() -> Self {
+        ApiFormat::Auto
+    }
+}
+impl ApiFormat {
+    /// turns `Auto` into the format that the body actually has
+    fn resolve(self, body: &str) -> ApiFormat {
+        match self {
+            ApiFormat::Auto => {
+                if jsonapi::is_json_api(body) {
+                    ApiFormat::JsonApi
+                } else {
+                    ApiFormat::Legacy
+                }
+            },
+            other => other,
+        }
+    }
+}
 impl DB_Conf {
     // used in yaml to be filled in at runtime
     const PLACEHOLDER_API_TOKEN: &'static str = "{api_token}";
@@ -257,10 +445,10 @@ impl DB_Conf {
             .replace(DB_Conf::PLACEHOLDER_SERVICE_TOKEN, &self.service_token)
     }
 
-    fn versand_endpoints(&self) -> impl Iterator<Item = String> + '_ {
+    fn versand_endpoints(&self) -> impl Iterator<Item = (String, ApiFormat)> + '_ {
         self.versand_endpoint_fmtstrs
             .iter()
-            .map(move |s| self.format_versand_endpoint(s.to_string()))
+            .map(move |e| (self.format_versand_endpoint(e.url().to_string()), e.format()))
             .into_iter()
     }
 }
@@ -268,33 +456,137 @@ impl DB_Conf {
 #[cfg(not(target_arch = "wasm32"))]
 fn get_data_for_versand(
     db_conf: &DB_Conf,
-) -> Result<ReasonableDataset, Box<dyn std::error::Error>> {
-    let mut endpoints = db_conf.versand_endpoints();
-    let endpoint = endpoints.next();
-    if endpoint.is_none() {
-        return Err(Box::new(std::io::Error::from(std::io::ErrorKind::Other)));
-    }
-
-    let endpoint = endpoint.unwrap();
-    let body = chttp::get(endpoint)?.into_body().text()?;
-    let mut reasonable_dataset = reasonablify_body(&body)?;
-    for endpoint in endpoints {
-        let body = chttp::get(endpoint)?.into_body().text()?;
-        let reasonable_ds = reasonablify_body(&body)?;
+    user_interface: &dyn DbparseInteractor,
+) -> Result<ReasonableDataset, Error> {
+    let pages = get_raw_pages_for_versand(db_conf, user_interface)?;
+    return reasonablify_pages(&pages);
+}
+
+/// downloads all pages of all endpoints without interpreting them yet
+#[cfg(not(target_arch = "wasm32"))]
+fn get_raw_pages_for_versand(
+    db_conf: &DB_Conf,
+    user_interface: &dyn DbparseInteractor,
+) -> Result<Vec<SnapshotPage>, Error> {
+    let mut pages: Vec<SnapshotPage> = Vec::new();
+    for (index, (endpoint, format)) in db_conf.versand_endpoints().enumerate() {
+        let mut endpoint_pages = get_all_pages(index + 1, endpoint, format)?;
+        user_interface.on_pages_fetched(index + 1, endpoint_pages.len());
+        pages.append(&mut endpoint_pages);
+    }
+
+    if pages.is_empty() {
+        return Err(Error::NoEndpoints);
+    }
+    return Ok(pages);
+}
+
+/// fetches the first page from `endpoint` and then keeps following the links to the next page
+/// until there are none left. Links that go round in circles or never end are an error, because
+/// stopping early would silently leave out people.
+#[cfg(not(target_arch = "wasm32"))]
+fn get_all_pages(
+    endpoint_number: usize,
+    endpoint: String,
+    format: ApiFormat,
+) -> Result<Vec<SnapshotPage>, Error> {
+    let mut visited: HashSet<String> = HashSet::new();
+    let mut pages: Vec<SnapshotPage> = Vec::new();
+    let mut next_link = Some(endpoint);
+
+    while let Some(link) = next_link {
+        if visited.contains(&link) || visited.len() >= MAX_PAGES_PER_ENDPOINT {
+            return Err(Error::Pagination {
+                endpoint_number: endpoint_number,
+                pages: visited.len(),
+            });
+        }
+        let body = get_body(endpoint_number, &link)?;
+        next_link = pagination::next_page_link(&body, &link);
+        pages.push(SnapshotPage {
+            endpoint_number: endpoint_number,
+            format: format.resolve(&body),
+            body: body,
+        });
+        visited.insert(link);
+    }
+
+    return Ok(pages);
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn get_body(endpoint_number: usize, link: &str) -> Result<String, Error> {
+    let network_error = |e: &dyn std::error::Error| Error::Network {
+        endpoint_number: endpoint_number,
+        reason: e.to_string(),
+    };
+    let response = chttp::get(link).map_err(|e| network_error(&e))?;
+    let status = response.status();
+    if status.as_u16() == 401 || status.as_u16() == 403 {
+        return Err(Error::Auth {
+            endpoint_number: endpoint_number,
+        });
+    }
+    if !status.is_success() {
+        return Err(Error::Http {
+            endpoint_number: endpoint_number,
+            status: status.as_u16(),
+        });
+    }
+    return response
+        .into_body()
+        .text()
+        .map_err(|e| network_error(&e));
+}
+
+/// parses all pages and merges them into one dataset
+pub fn reasonablify_pages(pages: &Vec<SnapshotPage>) -> Result<ReasonableDataset, Error> {
+    let mut reasonable_dataset = ReasonableDataset {
+        people: Vec::new(),
+        groups: HashSet::new(),
+    };
+    for page in pages.iter() {
+        let reasonable_ds = reasonablify_body(&page.body, page.format, page.endpoint_number)?;
         reasonable_dataset.extend(&reasonable_ds);
     }
 
+    if reasonable_dataset.people.len() < 1 {
+        return Err(Error::EmptyDataset);
+    }
+
     return Ok(reasonable_dataset);
 }
 
-fn track_usage() -> Result<(), Box<dyn std::error::Error>> {
-    let _body = chttp::get(USAGE_TRACKING_URL)?.into_body().text()?;
-    Ok(())
+fn reasonablify_body(
+    body: &String,
+    format: ApiFormat,
+    endpoint_number: usize,
+) -> Result<ReasonableDataset, Error> {
+    // hitobito answers wrong credentials with `{"error": "..."}` instead of an http error
+    if let Ok(value) = serde_json::from_str::<serde_json::Value>(body) {
+        if value.get("error").is_some() {
+            return Err(Error::Auth {
+                endpoint_number: endpoint_number,
+            });
+        }
+    }
+
+    // an empty page is not a problem in itself, only if all pages of all endpoints are empty
+    let reasonable_dataset: ReasonableDataset = match format.resolve(body) {
+        ApiFormat::JsonApi => jsonapi::to_reasonable_dataset(body, endpoint_number)?,
+        _ => reasonablify_legacy_body(body, endpoint_number)?,
+    };
+
+    Ok(reasonable_dataset)
 }
 
-fn reasonablify_body(body: &String) -> Result<ReasonableDataset, Box<dyn std::error::Error>> {
+fn reasonablify_legacy_body(
+    body: &String,
+    endpoint_number: usize,
+) -> Result<ReasonableDataset, Error> {
     // deserialize the json data into a struct
-    let dese: PeopleRequest = serde_json::from_str::<PeopleRequest>(body.as_ref())?;
+    let dese: PeopleRequest = serde_json::from_str::<PeopleRequest>(body.as_ref())
+        .map_err(|e| Error::parse(endpoint_number, &e))?;
 
     let mut i = 0;
 
@@ -306,12 +598,7 @@ fn reasonablify_body(body: &String) -> Result<ReasonableDataset, Box<dyn std::er
     }
 
     // transform the Person into a ReasonablePerson, which directly contains all relevant data
-    let reasonable_dataset: ReasonableDataset = dese.to_reasonable_dataset();
-    if reasonable_dataset.people.len() < 1 {
-        panic!("There are no people in the dataset!");
-    }
-
-    Ok(reasonable_dataset)
+    dese.to_reasonable_dataset()
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -351,6 +638,7 @@ struct Linked {
 ///"picture": "https://db.cevi.ch/assets/profil-3a8452c9ac8e8b1b70b9d4f4250417bea5be8a4518dbfae44db944f8fda07ca5.png",
 ///"salutation_parents": "Herr",
 ///"name_parents": "..., ...",
+///"birthday": "2008-04-01",
 ///"links": {
 /// "ortsgruppe": "115",
 ///     "phone_numbers": [
@@ -382,8 +670,17 @@ struct Person {
     zip_code: String,
     #[serde(with = "null_str_serder")]
     town: String,
+    /// missing in some older responses
+    #[serde(default, with = "null_str_serder")]
+    country: String,
+    /// e.g. "Herr und Frau", missing in some older responses
+    #[serde(default, with = "null_str_serder")]
+    salutation_parents: String,
     #[serde(with = "null_str_serder")]
     name_parents: String,
+    /// "YYYY-MM-DD", only delivered if the account may see it
+    #[serde(default, with = "null_str_serder")]
+    birthday: String,
     links: PersonLinks,
 }
 
@@ -451,6 +748,11 @@ impl Role {
             },
         }
     }
+
+    /// the id of the group in which the person has this role
+    pub fn group_id(&self) -> &str {
+        &self.links.group_id
+    }
 }
 
 type StringHashMapType<V> = HashMap<Rc<str>, V>;
@@ -579,49 +881,75 @@ pub struct ReasonableDataset {
     groups: HashSet<ReasonableGroup>,
 }
 impl ReasonableDataset {
-    fn get_groups(&self) -> &HashSet<ReasonableGroup> {
+    pub fn get_groups(&self) -> &HashSet<ReasonableGroup> {
         &self.groups
     }
 
-    /// ADDS people from new dataset, does not perform any checks whether they are already included
+    /// ADDS people from new dataset. People that are already included (same hitobito id) are not
+    /// added a second time, instead their roles and groups are merged into the existing entry.
     fn extend(&mut self, other: &Self) {
-        self.people.extend(other.people.clone());
+        let mut index_by_id: HashMap<usize, usize> = self
+            .people
+            .iter()
+            .enumerate()
+            .map(|(index, person)| (person.id, index))
+            .collect();
+        for person in other.people.iter() {
+            match index_by_id.get(&person.id) {
+                Some(&index) => self.people[index].merge(person),
+                None => {
+                    index_by_id.insert(person.id, self.people.len());
+                    self.people.push(person.clone());
+                },
+            }
+        }
         self.groups = self.groups.union(other.get_groups()).cloned().collect();
     }
 }
 // to get reasonable information, we want the group that is stored in Role:links, which is found
 // by id which we get from Person:links
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct ReasonablePerson {
+    /// the id of the person in hitobito
+    pub id: usize,
     pub first_name: String,
     pub last_name: String,
     pub nickname: String,
-    pub address: String,
-    pub zip_code: String,
-    pub town: String,
+    pub address: PostalAddress,
+    /// e.g. "Herr und Frau", empty if unknown
+    pub salutation_parents: String,
     pub name_parents: String,
+    /// "YYYY-MM-DD" as delivered by the database, empty if unknown
+    pub birthday: String,
     pub roles: HashSet<Role>,
     pub groups: HashSet<ReasonableGroup>,
 }
+impl ReasonablePerson {
+    /// adds the roles and groups of `other`, which is expected to be the same person fetched from
+    /// a different endpoint
+    fn merge(&mut self, other: &ReasonablePerson) {
+        self.roles.extend(other.roles.iter().cloned());
+        self.groups.extend(other.groups.iter().cloned());
+    }
+}
 impl PeopleRequest {
-    fn to_reasonable_dataset(&self) -> ReasonableDataset {
+    fn to_reasonable_dataset(&self) -> Result<ReasonableDataset, Error> {
         let mut all_groups: HashSet<ReasonableGroup> = HashSet::new();
         let mut all_people: Vec<ReasonablePerson> = Vec::<ReasonablePerson>::new();
-        if self.people.len() < 1 {
-            panic!("There are no people in the dataset!");
-        }
 
         print!("---\n");
         for p in self.people.iter() {
             let mut reasonable_person = ReasonablePerson {
+                id: p.id,
                 first_name: p.first_name.trim().to_string(),
                 last_name: p.last_name.trim().to_string(),
                 nickname: p.nickname.trim().to_string(),
-                address: p.address.trim().to_string(),
-                zip_code: p.zip_code.trim().to_string(),
-                town: p.town.trim().to_string(),
+                address: PostalAddress::parse(&p.address, &p.zip_code, &p.town)
+                    .with_country(&p.country),
+                salutation_parents: p.salutation_parents.trim().to_string(),
                 name_parents: p.name_parents.trim().to_string(),
+                birthday: p.birthday.trim().to_string(),
                 roles: HashSet::<Role>::new(),
                 groups: HashSet::<ReasonableGroup>::new(),
             };
@@ -629,11 +957,14 @@ impl PeopleRequest {
             // get roles directly
             for role_id in p.links.roles.iter() {
                 //let strx: String = as_string(role_id);
-                let role: &Role = self
-                    .linked
-                    .roles_map
-                    .gettt(role_id)
-                    .expect(&format!("role_id = {} does not exist", role_id));
+                let role: &Role =
+                    self.linked
+                        .roles_map
+                        .gettt(role_id)
+                        .ok_or(Error::DanglingRoleRef {
+                            person_id: p.id,
+                            role_id: role_id.clone(),
+                        })?;
                 reasonable_person.roles.insert(role.clone());
 
                 // get group corresponding to role (linked in Role links) (This could be optimized)
@@ -642,10 +973,10 @@ impl PeopleRequest {
                     .groups
                     .iter()
                     .find(|&grp| grp.id == role.links.group_id)
-                    .expect(&format!(
-                        "Group with id {} does not exist!",
-                        role.links.group_id
-                    ))
+                    .ok_or(Error::DanglingGroupRef {
+                        role_id: role.id.to_string(),
+                        group_id: role.links.group_id.clone(),
+                    })?
                     .clone();
                 let reasonable_group: ReasonableGroup = group.into();
                 reasonable_person.groups.insert(reasonable_group.clone());
@@ -661,14 +992,74 @@ impl PeopleRequest {
             print!("all_groups = {:#?}", all_groups);
         }
 
-        ReasonableDataset {
+        Ok(ReasonableDataset {
             people: all_people,
             groups: all_groups,
-        }
+        })
     }
 }
 
 pub trait DbparseInteractor {
     fn on_download_finished(&self);
     fn error_missing_config_file(&self, filename: String);
+    /// `endpoint_number` starts counting at 1, in the order of the endpoints in config.yaml
+    fn on_pages_fetched(&self, endpoint_number: usize, pages: usize);
+    /// only called if telemetry is enabled. Failing to send it is not a reason to stop.
+    fn on_telemetry_finished(&self, result: Result<(), &dyn std::error::Error>);
+    /// called once before an error is returned from `run`, `run_from_snapshot` or
+    /// `fetch_snapshot`
+    fn on_error(&self, error: &Error);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn person_with_role(id: usize, role_id: &str, group_id: &str) -> ReasonablePerson {
+        let mut person = ReasonablePerson {
+            id: id,
+            first_name: String::from("Eric"),
+            last_name: String::from("Mink"),
+            nickname: String::from("Levanzo"),
+            address: PostalAddress::parse("Musterstrasse 2", "8330", "Pfäffikon ZH"),
+            ..ReasonablePerson::default()
+        };
+        person.roles.insert(Role::new(
+            Rc::from(role_id),
+            String::from("Mitglied"),
+            None,
+            String::from(group_id),
+            String::from("115"),
+        ));
+        person.groups.insert(ReasonableGroup::from(Group {
+            id: String::from(group_id),
+            name: format!("Gruppe {}", group_id),
+            group_type: String::from("Stufe"),
+        }));
+        person
+    }
+
+    fn dataset_of(people: Vec<ReasonablePerson>) -> ReasonableDataset {
+        let groups = people.iter().flat_map(|p| p.groups.iter().cloned()).collect();
+        ReasonableDataset {
+            people: people,
+            groups: groups,
+        }
+    }
+
+    #[test]
+    fn test_extend_merges_same_person() {
+        let mut stufe = dataset_of(vec![person_with_role(6468, "1", "1341")]);
+        let abteilung = dataset_of(vec![
+            person_with_role(6468, "2", "115"),
+            person_with_role(42, "3", "115"),
+        ]);
+        stufe.extend(&abteilung);
+
+        assert_eq!(stufe.people.len(), 2);
+        let levanzo = stufe.people.iter().find(|p| p.id == 6468).unwrap();
+        assert_eq!(levanzo.roles.len(), 2);
+        assert_eq!(levanzo.groups.len(), 2);
+        assert_eq!(stufe.groups.len(), 2);
+    }
 }
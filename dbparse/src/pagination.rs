@@ -0,0 +1,102 @@
+//! The database does not return all people of a large group at once. Instead, the response
+//! contains a link to the next page:
+//!
+//! * JSON:API: `"links": { "next": "https://db.cevi.ch/api/people?page[number]=2&..." }`
+//! * legacy: `"next_page_link": "https://db.cevi.ch/groups/2423/people.json?page=2&..."`
+//!
+//! On the last page, the link is missing or `null`.
+
+/// query parameters that carry the credentials. If the database omits them in the next-page link,
+/// they are copied over from the link of the previous page.
+const AUTH_QUERY_PARAMETERS: [&str; 3] = ["token", "user_email", "user_token"];
+
+/// returns the absolute link to the page after the one in `body`, or `None` if this was the last
+/// page.
+pub fn next_page_link(body: &str, current_page_link: &str) -> Option<String> {
+    let value: serde_json::Value = serde_json::from_str(body).ok()?;
+    let next = value
+        .get("links")
+        .and_then(|links| links.get("next"))
+        .or_else(|| value.get("next_page_link"))
+        .and_then(|next| next.as_str())?
+        .trim();
+    if next.is_empty() {
+        return None;
+    }
+
+    let absolute = make_absolute(next, current_page_link);
+    return Some(copy_auth_parameters(&absolute, current_page_link));
+}
+
+/// turns "/groups/2423/people.json?page=2" into "https://db.cevi.ch/groups/2423/people.json?page=2"
+fn make_absolute(link: &str, current_page_link: &str) -> String {
+    if !link.starts_with('/') {
+        return link.to_string();
+    }
+    let scheme_end = current_page_link.find("://").map(|i| i + 3).unwrap_or(0);
+    let host_end = current_page_link[scheme_end..]
+        .find('/')
+        .map(|i| i + scheme_end)
+        .unwrap_or(current_page_link.len());
+    format!("{}{}", &current_page_link[..host_end], link)
+}
+
+fn copy_auth_parameters(link: &str, current_page_link: &str) -> String {
+    let mut result = link.to_string();
+    for (key, value) in query_parameters(current_page_link) {
+        if !AUTH_QUERY_PARAMETERS.contains(&key) {
+            continue;
+        }
+        if query_parameters(&result).any(|(k, _v)| k == key) {
+            continue;
+        }
+        let separator = if result.contains('?') { '&' } else { '?' };
+        result = format!("{}{}{}={}", result, separator, key, value);
+    }
+    return result;
+}
+
+fn query_parameters(link: &str) -> impl Iterator<Item = (&str, &str)> {
+    let query = link.splitn(2, '?').nth(1).unwrap_or("");
+    query.split('&').filter(|p| !p.is_empty()).map(|pair| {
+        let mut key_value = pair.splitn(2, '=');
+        (
+            key_value.next().unwrap_or(""),
+            key_value.next().unwrap_or(""),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_json_api_next_link() {
+        let body = r#"{"data": [], "links": {"next": "https://db.cevi.ch/api/people?page[number]=2&token=abc"}}"#;
+        let next = super::next_page_link(body, "https://db.cevi.ch/api/people?token=abc");
+        assert_eq!(
+            next,
+            Some("https://db.cevi.ch/api/people?page[number]=2&token=abc".to_string())
+        );
+    }
+
+    #[test]
+    fn test_last_page() {
+        let body = r#"{"data": [], "links": {"next": null}}"#;
+        assert_eq!(super::next_page_link(body, "https://db.cevi.ch/api/people"), None);
+        let body = r#"{"people": [], "linked": {}, "current_page": 3, "total_pages": 3}"#;
+        assert_eq!(super::next_page_link(body, "https://db.cevi.ch/api/people"), None);
+    }
+
+    #[test]
+    fn test_legacy_relative_link_keeps_token() {
+        let body = r#"{"people": [], "next_page_link": "/groups/2423/people.json?page=2"}"#;
+        let next = super::next_page_link(
+            body,
+            "https://db.cevi.ch/groups/2423/people.json?token=s3cr3t",
+        );
+        assert_eq!(
+            next,
+            Some("https://db.cevi.ch/groups/2423/people.json?page=2&token=s3cr3t".to_string())
+        );
+    }
+}
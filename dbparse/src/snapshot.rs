@@ -0,0 +1,59 @@
+//! A snapshot stores the raw responses of the database, so that the pipeline can be run again
+//! later without network access and without tokens.
+//!
+//! The endpoint links are deliberately not stored, because they contain the tokens.
+use super::{ApiFormat, Error};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Snapshot {
+    pub pages: Vec<SnapshotPage>,
+}
+
+/// one response body, exactly as it was received from the database
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SnapshotPage {
+    /// starts counting at 1, in the order of the endpoints in config.yaml
+    pub endpoint_number: usize,
+    #[serde(default)]
+    pub format: ApiFormat,
+    pub body: String,
+}
+
+impl Snapshot {
+    pub fn save(&self, path: &str) -> Result<(), Error> {
+        let writer = BufWriter::new(File::create(path).map_err(|e| Error::snapshot(path, &e))?);
+        serde_json::to_writer_pretty(writer, self).map_err(|e| Error::snapshot(path, &e))?;
+        Ok(())
+    }
+
+    pub fn load(path: &str) -> Result<Snapshot, Error> {
+        let reader = BufReader::new(File::open(path).map_err(|e| Error::snapshot(path, &e))?);
+        let snapshot: Snapshot =
+            serde_json::from_reader(reader).map_err(|e| Error::snapshot(path, &e))?;
+        Ok(snapshot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_snapshot_roundtrip() {
+        let snapshot = super::Snapshot {
+            pages: vec![super::SnapshotPage {
+                endpoint_number: 1,
+                format: super::ApiFormat::Auto,
+                body: String::from(r#"{"data": []}"#),
+            }],
+        };
+        let path = std::env::temp_dir().join("dbparse_test_snapshot_roundtrip.json");
+        let path = path.to_str().unwrap();
+        snapshot.save(path).expect("saving should work");
+        let loaded = super::Snapshot::load(path).expect("loading should work");
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(loaded.pages.len(), 1);
+        assert_eq!(loaded.pages[0].body, r#"{"data": []}"#);
+    }
+}
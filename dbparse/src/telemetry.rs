@@ -0,0 +1,51 @@
+//! Counting how often the tool is used. This is opt-in: nothing is sent unless config.yaml
+//! contains
+//!
+//! ```yaml
+//! telemetry:
+//!     enabled: true
+//! ```
+//!
+//! and the user did not pass `--no-telemetry`.
+use serde::{Deserialize, Serialize};
+
+const DEFAULT_USAGE_TRACKING_URL: &str =
+    "https://eric.mink.li/src/php/ccount/click.php?id=cevi_versand_usage";
+
+/// the `telemetry` section in config.yaml
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TelemetryConf {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_url")]
+    pub url: String,
+}
+impl Default for TelemetryConf {
+    fn default() -> Self {
+        TelemetryConf {
+            enabled: false,
+            url: default_url(),
+        }
+    }
+}
+
+fn default_url() -> String {
+    DEFAULT_USAGE_TRACKING_URL.to_string()
+}
+
+/// sends a single request to the configured url. Nothing about the user or the data is sent.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn track_usage(conf: &TelemetryConf) -> Result<(), Box<dyn std::error::Error>> {
+    let _body = chttp::get(conf.url.as_str())?.into_body().text()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_telemetry_is_off_by_default() {
+        let conf: super::TelemetryConf = serde_yaml::from_str("{}").unwrap();
+        assert!(!conf.enabled);
+        assert!(!super::TelemetryConf::default().enabled);
+    }
+}
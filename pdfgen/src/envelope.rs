@@ -0,0 +1,264 @@
+use serde::{Deserialize, Serialize};
+
+// The size of the couverts and where the address goes. The address positions follow the control
+// masks of the Swiss Post for couverts that are addressed on the right (see the post directory):
+// the address starts at least 10 mm below the franking zone (38 mm from the top), keeps 12 mm
+// from the right edge and stays above the coding zone (15 mm from the bottom).
+//
+// C5 keeps the position that has been used since the beginning, so that existing window couverts
+// still fit.
+
+/// All lengths in mm. The address position is the start of its first line, measured from the
+/// lower left corner, like everything in printpdf.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct EnvelopeDimensions {
+    pub width: f64,
+    pub height: f64,
+    pub address_x: f64,
+    pub address_y: f64,
+}
+
+impl EnvelopeDimensions {
+    /// a couvert of the given size with the address where the Swiss Post wants it
+    pub fn with_default_address(width: f64, height: f64) -> Self {
+        EnvelopeDimensions {
+            width: width,
+            height: height,
+            address_x: (width - RIGHT_MARGIN - ADDRESS_WIDTH).max(0.0),
+            address_y: (height - ADDRESS_TOP).max(ADDRESS_BOTTOM),
+        }
+    }
+
+    /// How many mm a line that starts at `x` may take, if its top is `top` mm above the bottom
+    /// edge: up to the right margin, or up to the franking zone for lines at its height.
+    pub fn space_right_of(&self, x: f64, top: f64) -> f64 {
+        let end = if top > self.height - FRANKING_HEIGHT {
+            self.width - FRANKING_WIDTH
+        } else {
+            self.width - RIGHT_MARGIN
+        };
+        return (end - x).max(0.0);
+    }
+}
+
+/// the zone at the top right for stamps and postage imprints
+const FRANKING_WIDTH: f64 = 74.0;
+const FRANKING_HEIGHT: f64 = 38.0;
+/// distance of the address from the right edge, including some space for long lines
+const RIGHT_MARGIN: f64 = 12.0;
+/// how wide the address block is expected to be
+const ADDRESS_WIDTH: f64 = 95.0;
+/// franking zone, 10 mm space, and the height of the first line
+const ADDRESS_TOP: f64 = FRANKING_HEIGHT + 10.0 + 6.0;
+/// coding zone and five lines of address
+const ADDRESS_BOTTOM: f64 = 15.0 + 32.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EnvelopeFormat {
+    /// 324 x 229 mm, for A4 unfolded
+    C4,
+    /// 229 x 162 mm, for A4 folded once
+    C5,
+    /// 162 x 114 mm, for A4 folded twice
+    C6,
+    /// 229 x 114 mm, for A4 folded twice lengthwise
+    C65,
+    /// 220 x 110 mm
+    DL,
+    Custom(EnvelopeDimensions),
+}
+
+impl Default for EnvelopeFormat {
+    fn default() -> Self {
+        EnvelopeFormat::C5
+    }
+}
+
+impl EnvelopeFormat {
+    pub fn dimensions(&self) -> EnvelopeDimensions {
+        match self {
+            EnvelopeFormat::C4 => EnvelopeDimensions::with_default_address(324.0, 229.0),
+            EnvelopeFormat::C5 => EnvelopeDimensions {
+                width: 229.0,
+                height: 162.0,
+                address_x: 120.0,
+                address_y: 65.0,
+            },
+            EnvelopeFormat::C6 => EnvelopeDimensions::with_default_address(162.0, 114.0),
+            EnvelopeFormat::C65 => EnvelopeDimensions::with_default_address(229.0, 114.0),
+            EnvelopeFormat::DL => EnvelopeDimensions::with_default_address(220.0, 110.0),
+            EnvelopeFormat::Custom(dimensions) => *dimensions,
+        }
+    }
+
+    /// e.g. "C6/5" or "200x100"
+    pub fn name(&self) -> String {
+        match self {
+            EnvelopeFormat::C4 => String::from("C4"),
+            EnvelopeFormat::C5 => String::from("C5"),
+            EnvelopeFormat::C6 => String::from("C6"),
+            EnvelopeFormat::C65 => String::from("C6/5"),
+            EnvelopeFormat::DL => String::from("DL"),
+            EnvelopeFormat::Custom(dimensions) => {
+                format!("{}x{}", dimensions.width, dimensions.height)
+            },
+        }
+    }
+}
+
+impl std::str::FromStr for EnvelopeFormat {
+    type Err = String;
+
+    /// "C4", "C5", "C6", "C6/5", "DL", or "<width>x<height>" in mm
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_uppercase().as_str() {
+            "C4" => Ok(EnvelopeFormat::C4),
+            "C5" => Ok(EnvelopeFormat::C5),
+            "C6" => Ok(EnvelopeFormat::C6),
+            "C6/5" | "C65" => Ok(EnvelopeFormat::C65),
+            "DL" => Ok(EnvelopeFormat::DL),
+            other => {
+                let size: Vec<f64> = other
+                    .split('X')
+                    .map(|part| part.trim().parse::<f64>())
+                    .collect::<Result<Vec<f64>, _>>()
+                    .map_err(|_| unknown_format(s))?;
+                match size.as_slice() {
+                    [width, height] if *width > 0.0 && *height > 0.0 => Ok(EnvelopeFormat::Custom(
+                        EnvelopeDimensions::with_default_address(*width, *height),
+                    )),
+                    _ => Err(unknown_format(s)),
+                }
+            },
+        }
+    }
+}
+
+/// the `envelope` section of config.yaml, e.g.
+/// ```yaml
+/// envelope:
+///     format: custom
+///     width: 220
+///     height: 110
+///     address_x: 110
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct EnvelopeConfig {
+    /// like on the command line, or "custom" with `width` and `height`
+    pub format: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub width: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub height: Option<f64>,
+    /// moves the address of any format, e.g. to fit a different window
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address_x: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address_y: Option<f64>,
+}
+
+impl EnvelopeConfig {
+    pub fn to_format(&self) -> Result<EnvelopeFormat, String> {
+        let format = if self.format.trim().eq_ignore_ascii_case("custom") {
+            match (self.width, self.height) {
+                (Some(width), Some(height)) => EnvelopeFormat::Custom(
+                    EnvelopeDimensions::with_default_address(width, height),
+                ),
+                _ => return Err(String::from("format custom needs width and height")),
+            }
+        } else {
+            self.format.parse::<EnvelopeFormat>()?
+        };
+        if self.address_x.is_none() && self.address_y.is_none() {
+            return Ok(format);
+        }
+        let mut dimensions = format.dimensions();
+        dimensions.address_x = self.address_x.unwrap_or(dimensions.address_x);
+        dimensions.address_y = self.address_y.unwrap_or(dimensions.address_y);
+        Ok(EnvelopeFormat::Custom(dimensions))
+    }
+}
+
+fn unknown_format(s: &str) -> String {
+    format!(
+        "unknown envelope format {:?}, expected C4, C5, C6, C6/5, DL or a size like 220x110",
+        s
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EnvelopeDimensions, EnvelopeFormat};
+
+    #[test]
+    fn test_parse_formats() {
+        assert_eq!("c6/5".parse::<EnvelopeFormat>(), Ok(EnvelopeFormat::C65));
+        assert_eq!("DL".parse::<EnvelopeFormat>(), Ok(EnvelopeFormat::DL));
+        assert_eq!(
+            "200x100".parse::<EnvelopeFormat>(),
+            Ok(EnvelopeFormat::Custom(
+                EnvelopeDimensions::with_default_address(200.0, 100.0)
+            ))
+        );
+        assert!("B5".parse::<EnvelopeFormat>().is_err());
+        assert!("200x".parse::<EnvelopeFormat>().is_err());
+    }
+
+    #[test]
+    fn test_config() {
+        let config = super::EnvelopeConfig {
+            format: String::from("C6/5"),
+            address_y: Some(50.0),
+            ..super::EnvelopeConfig::default()
+        };
+        let dimensions = config.to_format().unwrap().dimensions();
+        assert_eq!(dimensions.width, 229.0);
+        assert_eq!(dimensions.address_y, 50.0);
+
+        let config = super::EnvelopeConfig {
+            format: String::from("custom"),
+            ..super::EnvelopeConfig::default()
+        };
+        assert!(config.to_format().is_err());
+    }
+
+    #[test]
+    fn test_address_stays_out_of_the_post_zones() {
+        for format in vec![
+            EnvelopeFormat::C4,
+            EnvelopeFormat::C5,
+            EnvelopeFormat::C6,
+            EnvelopeFormat::C65,
+            EnvelopeFormat::DL,
+        ] {
+            let dimensions = format.dimensions();
+            assert!(
+                dimensions.address_x + super::ADDRESS_WIDTH + super::RIGHT_MARGIN
+                    <= dimensions.width,
+                "{}",
+                format.name()
+            );
+            assert!(
+                dimensions.address_y <= dimensions.height - super::ADDRESS_TOP,
+                "{}",
+                format.name()
+            );
+            assert!(
+                dimensions.address_y >= super::ADDRESS_BOTTOM,
+                "{}",
+                format.name()
+            );
+        }
+    }
+
+    #[test]
+    fn test_space_right_of_ends_before_the_franking_zone() {
+        let c5 = EnvelopeFormat::C5.dimensions();
+        // the names of the default layout, 18 mm from the top
+        assert_eq!(c5.space_right_of(32.0, 162.0 - 18.0), 229.0 - 74.0 - 32.0);
+        // the address, below it
+        assert_eq!(c5.space_right_of(120.0, 65.0 + 6.0), 229.0 - 12.0 - 120.0);
+        // a text that starts in the franking zone gets no space at all
+        assert_eq!(c5.space_right_of(200.0, 160.0), 0.0);
+    }
+}
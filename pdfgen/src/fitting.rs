@@ -0,0 +1,213 @@
+use crate::fonts::FontFile;
+
+// Makes texts fit into the space they have. A household of five with long group names, or a long
+// street, would otherwise run off the couvert or into the address window. Texts are measured with
+// the metrics of the font they are printed in. Too long texts are first made smaller, down to
+// `SMALLEST_SCALE` of their size, and only then cut off with an ellipsis.
+
+const MM_PER_PT: f64 = 25.4 / 72.0;
+/// how small a text may become, relative to its size in the layout
+const SMALLEST_SCALE: f64 = 0.7;
+const ELLIPSIS: &str = "…";
+
+/// lines that fit into their width when printed in `size`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fitted {
+    pub lines: Vec<String>,
+    pub size: i64,
+    /// printed smaller than asked for
+    pub shrunk: bool,
+    /// some lines were cut off
+    pub truncated: bool,
+}
+
+impl FontFile {
+    /// how wide `text` is in mm when printed in `size` pt
+    pub fn text_width(&self, text: &str, size: i64) -> f64 {
+        let v_metrics = self.font.v_metrics_unscaled();
+        // rusttype scales by the height from descent to ascent, not by the em like pdf does
+        let scale = rusttype::Scale::uniform(
+            (v_metrics.ascent - v_metrics.descent) / self.font.units_per_em() as f32 * size as f32,
+        );
+        let width_pt: f32 = text
+            .chars()
+            .map(|c| self.font.glyph(c).scaled(scale).h_metrics().advance_width)
+            .sum();
+        return width_pt as f64 * MM_PER_PT;
+    }
+
+    /// `text` without as many characters at its end as needed to fit with an ellipsis
+    fn truncate(&self, text: &str, size: i64, max_width: f64) -> String {
+        let mut chars: Vec<char> = text.chars().collect();
+        while !chars.is_empty() {
+            let shortened = format!(
+                "{}{}",
+                chars.iter().collect::<String>().trim_end(),
+                ELLIPSIS
+            );
+            if self.text_width(&shortened, size) <= max_width {
+                return shortened;
+            }
+            chars.pop();
+        }
+        return String::from(ELLIPSIS);
+    }
+}
+
+/// Fits all `lines` into `max_width` mm with the same size, so that a block like the address stays
+/// even. Lines that do not fit even at the smallest size are cut off.
+pub fn fit_lines(font: &FontFile, lines: &[String], size: i64, max_width: f64) -> Fitted {
+    let widest = lines
+        .iter()
+        .map(|line| font.text_width(line, size))
+        .fold(0.0, f64::max);
+    if widest <= max_width {
+        return Fitted {
+            lines: lines.to_vec(),
+            size: size,
+            shrunk: false,
+            truncated: false,
+        };
+    }
+
+    // the width grows with the size, so the size that fits can be calculated
+    let smallest = ((size as f64 * SMALLEST_SCALE).floor() as i64).max(1);
+    let fitting = (size as f64 * max_width / widest).floor() as i64;
+    if fitting >= smallest {
+        return Fitted {
+            lines: lines.to_vec(),
+            size: fitting,
+            shrunk: true,
+            truncated: false,
+        };
+    }
+    let lines: Vec<String> = lines
+        .iter()
+        .map(|line| {
+            if font.text_width(line, smallest) <= max_width {
+                line.clone()
+            } else {
+                font.truncate(line, smallest, max_width)
+            }
+        })
+        .collect();
+    Fitted {
+        lines: lines,
+        size: smallest,
+        shrunk: smallest < size,
+        truncated: true,
+    }
+}
+
+/// like `fit_lines`, and tells the user when the lines had to be made smaller or cut off
+pub(crate) fn fit_and_report(
+    user_interface: Option<&dyn ui::UserInteractor>,
+    font: &FontFile,
+    lines: &[String],
+    size: i64,
+    max_width: f64,
+) -> Fitted {
+    let fitted = fit_lines(font, lines, size, max_width);
+    if fitted.shrunk || fitted.truncated {
+        if let Some(ui) = user_interface {
+            ui.report_text_too_long(&lines.join(", "), fitted.size, fitted.truncated);
+        }
+    }
+    return fitted;
+}
+
+/// Splits `text` into lines that fit into `max_width` mm, at spaces. Words that are wider than a
+/// line get a line of their own and are cut off.
+pub fn wrap(font: &FontFile, text: &str, size: i64, max_width: f64) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        let longer = if line.is_empty() {
+            String::from(word)
+        } else {
+            format!("{} {}", line, word)
+        };
+        if line.is_empty() || font.text_width(&longer, size) <= max_width {
+            line = longer;
+        } else {
+            lines.push(std::mem::replace(&mut line, String::from(word)));
+        }
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    return lines
+        .into_iter()
+        .map(|line| {
+            if font.text_width(&line, size) <= max_width {
+                line
+            } else {
+                font.truncate(&line, size, max_width)
+            }
+        })
+        .collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fit_lines, wrap};
+    use crate::fonts::Fonts;
+
+    #[test]
+    fn test_text_width_grows_with_size_and_length() {
+        let font = &Fonts::default().regular;
+        let width = font.text_width("Familie Muster", 10);
+        assert!(width > 20.0 && width < 40.0, "{}", width);
+        assert!((font.text_width("Familie Muster", 20) - 2.0 * width).abs() < 0.01);
+        assert!(font.text_width("Familie Muster-Beispiel", 10) > width);
+        assert_eq!(font.text_width("", 10), 0.0);
+    }
+
+    #[test]
+    fn test_fit_lines_shrinks_then_truncates() {
+        let font = &Fonts::default().regular;
+        let lines = vec![
+            String::from("Familie Muster"),
+            String::from("8332 Mustrikon"),
+        ];
+        // the digits make the second line the wider one
+        let width = font.text_width("8332 Mustrikon", 10);
+        assert!(width > font.text_width("Familie Muster", 10));
+
+        let fitted = fit_lines(font, &lines, 10, width + 1.0);
+        assert_eq!(
+            (fitted.size, fitted.shrunk, fitted.truncated),
+            (10, false, false)
+        );
+
+        let fitted = fit_lines(font, &lines, 10, width * 0.85);
+        assert_eq!(
+            (fitted.size, fitted.shrunk, fitted.truncated),
+            (8, true, false)
+        );
+        assert_eq!(fitted.lines, lines);
+
+        let fitted = fit_lines(font, &lines, 10, width * 0.5);
+        assert_eq!(
+            (fitted.size, fitted.shrunk, fitted.truncated),
+            (7, true, true)
+        );
+        assert!(fitted.lines[0].starts_with("Familie") && fitted.lines[0].ends_with('…'));
+        assert!(fitted
+            .lines
+            .iter()
+            .all(|line| font.text_width(line, 7) <= width * 0.5));
+    }
+
+    #[test]
+    fn test_wrap() {
+        let font = &Fonts::default().regular;
+        let width = font.text_width("Levanzo und", 10);
+        assert_eq!(
+            wrap(font, "Focus, Levanzo und Pseudo", 10, width),
+            vec!["Focus,", "Levanzo und", "Pseudo"]
+        );
+        assert!(wrap(font, "  ", 10, width).is_empty());
+        assert!(wrap(font, "Sommerlagerinformationsbroschüre", 10, width)[0].ends_with('…'));
+    }
+}
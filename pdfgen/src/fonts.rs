@@ -0,0 +1,283 @@
+use crate::layout::{FontStyle, Layout};
+use crate::CouvertInfo;
+use serde::{Deserialize, Serialize};
+
+// The fonts of the couverts. DejaVu Sans is bundled because it may be redistributed and covers
+// far more letters than the names in our database need. Other fonts can be set per role in the
+// `fonts` section of layout.yaml, e.g. Calibri from C:\Windows\Fonts. A configured font replaces
+// both the regular and the light style for its role.
+//
+// Before printing, every text is checked against the font it will be printed in, so that a name
+// with a letter the font does not have is noticed before the couverts are posted.
+
+const DEJAVU_FONT: &'static [u8] = include_bytes!("../res/fonts/DejaVuSans.ttf");
+const DEJAVU_LIGHT_FONT: &'static [u8] = include_bytes!("../res/fonts/DejaVuSans-ExtraLight.ttf");
+
+/// which texts a font is used for
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FontRole {
+    /// the address, also on labels and letters
+    Address,
+    /// the names, the groups, the message and the free texts
+    Names,
+    /// the text on the badges
+    Badges,
+}
+
+/// paths to TTF or OTF files, the bundled font for every role that is not set
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct FontPaths {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub names: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub badges: Option<String>,
+}
+
+/// a font that has been read, with its name for messages
+pub struct FontFile {
+    pub name: String,
+    bytes: Vec<u8>,
+    pub(crate) font: rusttype::Font<'static>,
+}
+
+impl FontFile {
+    /// fails if `bytes` are not a TTF or OTF font
+    pub fn new(name: &str, bytes: Vec<u8>) -> Result<Self, String> {
+        let font = rusttype::Font::from_bytes(bytes.clone()).map_err(|e| e.to_string())?;
+        Ok(FontFile {
+            name: String::from(name),
+            bytes: bytes,
+            font: font,
+        })
+    }
+
+    fn bundled(name: &str, bytes: &[u8]) -> Self {
+        FontFile::new(name, bytes.to_vec()).expect("the bundled fonts are fonts")
+    }
+
+    /// the characters of `text` that the font has no glyph for, each once
+    pub fn missing_glyphs(&self, text: &str) -> Vec<char> {
+        let mut missing: Vec<char> = Vec::new();
+        for c in text
+            .chars()
+            .filter(|c| !c.is_whitespace() && !c.is_control())
+        {
+            // glyph 0 is the box that is printed for characters the font does not have
+            if self.font.glyph(c).id().0 == 0 && !missing.contains(&c) {
+                missing.push(c);
+            }
+        }
+        return missing;
+    }
+}
+
+/// all fonts a document is printed with
+pub struct Fonts {
+    pub regular: FontFile,
+    pub light: FontFile,
+    pub address: Option<FontFile>,
+    pub names: Option<FontFile>,
+    pub badges: Option<FontFile>,
+}
+
+impl Default for Fonts {
+    /// only the bundled fonts
+    fn default() -> Self {
+        Fonts {
+            regular: FontFile::bundled("DejaVu Sans", DEJAVU_FONT),
+            light: FontFile::bundled("DejaVu Sans ExtraLight", DEJAVU_LIGHT_FONT),
+            address: None,
+            names: None,
+            badges: None,
+        }
+    }
+}
+
+impl Fonts {
+    /// Reads the configured fonts. A font that cannot be read or is not a font is reported, and
+    /// the bundled font is used for its role instead.
+    pub fn load(paths: &FontPaths, user_interface: &dyn ui::UserInteractor) -> Self {
+        let load = |path: &Option<String>| -> Option<FontFile> {
+            let path = path.as_ref()?;
+            let font = std::fs::read(path)
+                .map_err(|e| e.to_string())
+                .and_then(|bytes| FontFile::new(path, bytes));
+            match font {
+                Ok(font) => Some(font),
+                Err(reason) => {
+                    user_interface.error_loading_font(path, &reason);
+                    None
+                },
+            }
+        };
+        Fonts {
+            address: load(&paths.address),
+            names: load(&paths.names),
+            badges: load(&paths.badges),
+            ..Fonts::default()
+        }
+    }
+
+    /// the configured font of the role, or the bundled font in the style
+    pub fn get(&self, role: FontRole, style: FontStyle) -> &FontFile {
+        let configured = match role {
+            FontRole::Address => &self.address,
+            FontRole::Names => &self.names,
+            FontRole::Badges => &self.badges,
+        };
+        match (configured, style) {
+            (Some(font), _) => font,
+            (None, FontStyle::Regular) => &self.regular,
+            (None, FontStyle::Light) => &self.light,
+        }
+    }
+
+    /// Every text of `couverts` whose font lacks some of its letters, e.g.
+    /// "Nguyễn Văn An: ễ, ă fehlen in DejaVu Sans ExtraLight". Each text is listed once.
+    pub fn preflight(&self, couverts: &Vec<CouvertInfo>, layout: &Layout) -> Vec<String> {
+        let mut texts: Vec<(String, &FontFile)> = Vec::new();
+        let address_font = self.get(FontRole::Address, layout.address.font);
+        let names_font = self.get(FontRole::Names, layout.names.font);
+        let groups_font = self.get(FontRole::Names, layout.groups.font);
+        let badges_font = self.get(FontRole::Badges, layout.badges.font);
+        for couvert in couverts.iter() {
+            for line in couvert.address.iter().chain(couvert.country.iter()) {
+                texts.push((line.clone(), address_font));
+            }
+            for receiver in couvert.receivers.iter() {
+                texts.push((receiver.nickname.clone(), names_font));
+                texts.push((receiver.group.clone(), groups_font));
+                texts.push((receiver.role.value(), badges_font));
+            }
+            if let Some(message) = &layout.message {
+                if let Some(template) = message.template_for(couvert) {
+                    texts.push((
+                        template.render(couvert),
+                        self.get(FontRole::Names, message.font),
+                    ));
+                }
+            }
+        }
+        for free_text in layout.free_text.iter() {
+            texts.push((
+                free_text.text.clone(),
+                self.get(FontRole::Names, free_text.font),
+            ));
+        }
+
+        let mut problems: Vec<String> = Vec::new();
+        for (text, font) in texts {
+            let missing = font.missing_glyphs(&text);
+            if missing.is_empty() {
+                continue;
+            }
+            let problem = format!(
+                "{}: {} {} in {}",
+                text,
+                missing
+                    .iter()
+                    .map(char::to_string)
+                    .collect::<Vec<String>>()
+                    .join(", "),
+                if missing.len() == 1 {
+                    "fehlt"
+                } else {
+                    "fehlen"
+                },
+                font.name
+            );
+            if !problems.contains(&problem) {
+                problems.push(problem);
+            }
+        }
+        return problems;
+    }
+}
+
+/// the fonts of `Fonts`, added to a pdf document
+pub(crate) struct PdfFonts {
+    pub regular: printpdf::IndirectFontRef,
+    pub light: printpdf::IndirectFontRef,
+    address: Option<printpdf::IndirectFontRef>,
+    names: Option<printpdf::IndirectFontRef>,
+    badges: Option<printpdf::IndirectFontRef>,
+}
+
+impl PdfFonts {
+    pub fn add_to(doc: &printpdf::PdfDocumentReference, fonts: &Fonts) -> Self {
+        let add = |font: &FontFile| {
+            doc.add_external_font(&mut std::io::Cursor::new(&font.bytes[..]))
+                .expect("Failed to load font")
+        };
+        PdfFonts {
+            regular: add(&fonts.regular),
+            light: add(&fonts.light),
+            address: fonts.address.as_ref().map(add),
+            names: fonts.names.as_ref().map(add),
+            badges: fonts.badges.as_ref().map(add),
+        }
+    }
+
+    /// like `Fonts::get`
+    pub fn get(&self, role: FontRole, style: FontStyle) -> &printpdf::IndirectFontRef {
+        let configured = match role {
+            FontRole::Address => &self.address,
+            FontRole::Names => &self.names,
+            FontRole::Badges => &self.badges,
+        };
+        match (configured, style) {
+            (Some(font), _) => font,
+            (None, FontStyle::Regular) => &self.regular,
+            (None, FontStyle::Light) => &self.light,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Fonts;
+    use crate::layout::{FontStyle, Layout};
+
+    #[test]
+    fn test_bundled_font_has_european_letters() {
+        let fonts = Fonts::default();
+        assert!(fonts
+            .regular
+            .missing_glyphs("Zoë Ødegård, Łódź, Dvořák")
+            .is_empty());
+        assert_eq!(
+            fonts.regular.missing_glyphs("Anna 山田 山"),
+            vec!['山', '田']
+        );
+    }
+
+    #[test]
+    fn test_preflight_names_the_text_and_font() {
+        let couvert = |nickname: &str| crate::CouvertInfo {
+            receivers: vec![crate::Receiver {
+                nickname: String::from(nickname),
+                group: String::from("Holon"),
+                role: crate::Role::Leiter,
+            }],
+            address: vec![
+                String::from("Familie Muster"),
+                String::from("8332 Mustrikon"),
+            ],
+            country: None,
+        };
+        let fonts = Fonts::default();
+        assert!(fonts
+            .preflight(&vec![couvert("Levanzo")], &Layout::default())
+            .is_empty());
+
+        let mut layout = Layout::default();
+        layout.names.font = FontStyle::Regular;
+        assert_eq!(
+            fonts.preflight(&vec![couvert("山田"), couvert("山田")], &layout),
+            vec![String::from("山田: 山, 田 fehlen in DejaVu Sans")]
+        );
+    }
+}
@@ -0,0 +1,289 @@
+use crate::fonts::{FontRole, Fonts, PdfFonts};
+use crate::layout::FontStyle;
+use crate::CouvertInfo;
+use serde::{Deserialize, Serialize};
+
+// Address labels on A4 sheets, for mailings in couverts that are bought pre-printed. A sheet is a
+// grid of equal labels, filled row by row from the top left. Partially used sheets can be used up
+// by skipping the labels that are already gone.
+
+const A4_WIDTH: f64 = 210.0;
+const A4_HEIGHT: f64 = 297.0;
+const MM_PER_PT: f64 = 25.4 / 72.0;
+
+/// All lengths in mm, measured from the top left corner of the sheet.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct LabelSheet {
+    pub rows: usize,
+    pub columns: usize,
+    pub label_width: f64,
+    pub label_height: f64,
+    /// to the top edge of the first row
+    pub margin_top: f64,
+    /// to the left edge of the first column
+    pub margin_left: f64,
+    /// from the left edge of one label to the left edge of the next. The label width if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pitch_x: Option<f64>,
+    /// from the top edge of one label to the top edge of the next. The label height if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pitch_y: Option<f64>,
+    /// between the edge of the label and the address
+    #[serde(default = "default_padding")]
+    pub padding: f64,
+    #[serde(default = "default_font_size")]
+    pub font_size: i64,
+}
+
+fn default_padding() -> f64 {
+    5.0
+}
+
+fn default_font_size() -> i64 {
+    10
+}
+
+/// The label sheets we buy. Sheets of other brands with the same grid work as well.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LabelFormat {
+    /// 3 x 7 labels of 63.5 x 38.1 mm
+    L7160,
+    /// 2 x 7 labels of 99.1 x 38.1 mm
+    L7163,
+    /// 3 x 8 labels of 70 x 36 mm
+    Zweckform3475,
+    /// 3 x 8 labels of 70 x 35 mm
+    Zweckform3422,
+    /// the `labels` section of config.yaml, a `LabelSheet`
+    Config,
+}
+
+impl std::str::FromStr for LabelFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_uppercase().as_str() {
+            "L7160" => Ok(LabelFormat::L7160),
+            "L7163" => Ok(LabelFormat::L7163),
+            "3475" => Ok(LabelFormat::Zweckform3475),
+            "3422" => Ok(LabelFormat::Zweckform3422),
+            "CONFIG" => Ok(LabelFormat::Config),
+            _ => Err(format!(
+                "unknown label sheet {:?}, expected L7160, L7163, 3475, 3422 or config",
+                s
+            )),
+        }
+    }
+}
+
+impl LabelFormat {
+    /// `None` for `Config`, which has to be read from config.yaml
+    pub fn sheet(&self) -> Option<LabelSheet> {
+        let sheet = |rows, columns, label_width, label_height, margin_top, margin_left, pitch_x| {
+            LabelSheet {
+                rows: rows,
+                columns: columns,
+                label_width: label_width,
+                label_height: label_height,
+                margin_top: margin_top,
+                margin_left: margin_left,
+                pitch_x: pitch_x,
+                pitch_y: None,
+                padding: default_padding(),
+                font_size: default_font_size(),
+            }
+        };
+        match self {
+            LabelFormat::L7160 => Some(sheet(7, 3, 63.5, 38.1, 15.15, 7.21, Some(66.04))),
+            LabelFormat::L7163 => Some(sheet(7, 2, 99.1, 38.1, 15.15, 4.65, Some(101.6))),
+            LabelFormat::Zweckform3475 => Some(sheet(8, 3, 70.0, 36.0, 4.5, 0.0, None)),
+            LabelFormat::Zweckform3422 => Some(sheet(8, 3, 70.0, 35.0, 8.5, 0.0, None)),
+            LabelFormat::Config => None,
+        }
+    }
+}
+
+impl LabelSheet {
+    pub fn labels_per_sheet(&self) -> usize {
+        self.rows * self.columns
+    }
+
+    /// Checks what `label_positions` and the layout divide by or fit text into, so a `labels`
+    /// section from config.yaml cannot make the label pdf panic or come out empty.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.rows < 1 || self.columns < 1 {
+            return Err(format!(
+                "`rows` and `columns` must be at least 1, not {} and {}",
+                self.rows, self.columns
+            ));
+        }
+        let lengths = vec![
+            ("label_width", Some(self.label_width)),
+            ("label_height", Some(self.label_height)),
+            ("pitch_x", self.pitch_x),
+            ("pitch_y", self.pitch_y),
+        ];
+        for (name, length) in lengths {
+            if let Some(length) = length {
+                if length.is_nan() || length <= 0.0 {
+                    return Err(format!("`{}` must be larger than 0, not {}", name, length));
+                }
+            }
+        }
+        if self.padding.is_nan() || self.padding < 0.0 {
+            return Err(format!("`padding` must not be negative, not {}", self.padding));
+        }
+        if self.font_size < 1 {
+            return Err(format!("`font_size` must be at least 1, not {}", self.font_size));
+        }
+        return Ok(());
+    }
+
+    /// Which sheet each label goes on, and the top left corner of the label in mm from the top
+    /// left corner of that sheet. The first `start_offset` labels of the first sheet are skipped.
+    fn label_positions(&self, start_offset: usize, count: usize) -> Vec<(usize, f64, f64)> {
+        let pitch_x = self.pitch_x.unwrap_or(self.label_width);
+        let pitch_y = self.pitch_y.unwrap_or(self.label_height);
+        (start_offset..start_offset + count)
+            .map(|slot| {
+                let on_sheet = slot % self.labels_per_sheet();
+                let row = on_sheet / self.columns;
+                let column = on_sheet % self.columns;
+                (
+                    slot / self.labels_per_sheet(),
+                    self.margin_left + column as f64 * pitch_x,
+                    self.margin_top + row as f64 * pitch_y,
+                )
+            })
+            .collect()
+    }
+}
+
+/// One label per couvert with its address, on as many A4 sheets as needed. `start_offset` labels
+/// are left empty on the first sheet, because they have been used before.
+pub fn generate_labels(
+    couverts: &Vec<CouvertInfo>,
+    user_interface: Option<&dyn ui::UserInteractor>,
+    sheet: &LabelSheet,
+    start_offset: usize,
+    fonts: &Fonts,
+) -> printpdf::PdfDocumentReference {
+    use printpdf::*;
+
+    let page_width = Mm(A4_WIDTH);
+    let page_height = Mm(A4_HEIGHT);
+
+    let (doc, first_page, first_layer) =
+        PdfDocument::new("Etiketten", page_width, page_height, "Layer 1");
+    let pdf_fonts = PdfFonts::add_to(&doc, fonts);
+    let font = pdf_fonts.get(FontRole::Address, FontStyle::Light);
+    let font_file = fonts.get(FontRole::Address, FontStyle::Light);
+
+    let mut layers = vec![doc.get_page(first_page).get_layer(first_layer)];
+    let positions = sheet.label_positions(start_offset, couverts.len());
+    for (couvert, (page, label_x, label_y)) in couverts.iter().zip(positions) {
+        while layers.len() <= page {
+            let (next_page, next_layer) = doc.add_page(
+                page_width,
+                page_height,
+                format!("Bogen {}, Layer 1", layers.len() + 1),
+            );
+            layers.push(doc.get_page(next_page).get_layer(next_layer));
+        }
+        let current_layer = &layers[page];
+
+        let address = crate::fitting::fit_and_report(
+            user_interface,
+            font_file,
+            &couvert
+                .address
+                .iter()
+                .chain(couvert.country.iter())
+                .cloned()
+                .collect::<Vec<String>>(),
+            sheet.font_size,
+            sheet.label_width - 2.0 * sheet.padding,
+        );
+
+        // the first line starts one line below the padding, text is placed by its baseline
+        let first_line_y = A4_HEIGHT - label_y - sheet.padding - address.size as f64 * MM_PER_PT;
+        current_layer.begin_text_section();
+        current_layer.set_font(font, address.size);
+        current_layer.set_text_cursor(Mm(label_x + sheet.padding), Mm(first_line_y));
+        current_layer.set_line_height(address.size);
+        for line in address.lines {
+            current_layer.write_text(line, font);
+            current_layer.add_line_break();
+        }
+        current_layer.end_text_section();
+    }
+
+    if let Some(ui) = user_interface {
+        ui.on_pdf_generation_finished();
+    }
+
+    return doc;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LabelFormat;
+
+    #[test]
+    fn test_presets_fit_on_a4() {
+        for format in vec![
+            LabelFormat::L7160,
+            LabelFormat::L7163,
+            LabelFormat::Zweckform3475,
+            LabelFormat::Zweckform3422,
+        ] {
+            let sheet = format.sheet().unwrap();
+            let last = sheet.label_positions(sheet.labels_per_sheet() - 1, 1)[0];
+            assert_eq!(last.0, 0, "{:?}", format);
+            assert!(
+                last.1 + sheet.label_width <= super::A4_WIDTH + 0.01,
+                "{:?}",
+                format
+            );
+            assert!(
+                last.2 + sheet.label_height <= super::A4_HEIGHT + 0.01,
+                "{:?}",
+                format
+            );
+        }
+        assert_eq!(LabelFormat::Config.sheet(), None);
+    }
+
+    #[test]
+    fn test_start_offset_skips_used_labels() {
+        let sheet = "3475".parse::<LabelFormat>().unwrap().sheet().unwrap();
+        let positions = sheet.label_positions(22, 3);
+        assert_eq!(positions[0], (0, 70.0, 4.5 + 7.0 * 36.0));
+        assert_eq!(positions[1], (0, 140.0, 4.5 + 7.0 * 36.0));
+        // the third label starts the next sheet
+        assert_eq!(positions[2], (1, 0.0, 4.5));
+    }
+
+    #[test]
+    fn test_validate() {
+        let sheet = LabelFormat::L7160.sheet().unwrap();
+        assert_eq!(sheet.validate(), Ok(()));
+
+        let mut no_columns = sheet.clone();
+        no_columns.columns = 0;
+        assert!(no_columns.validate().is_err());
+
+        let mut flat = sheet.clone();
+        flat.label_height = 0.0;
+        assert!(flat.validate().is_err());
+
+        let mut backwards = sheet.clone();
+        backwards.pitch_x = Some(-66.04);
+        assert!(backwards.validate().is_err());
+
+        let mut tiny = sheet.clone();
+        tiny.font_size = 0;
+        assert!(tiny.validate().is_err());
+    }
+}
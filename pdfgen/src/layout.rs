@@ -0,0 +1,470 @@
+use crate::fonts::FontPaths;
+use crate::placeholders::MessageTemplate;
+use crate::CouvertInfo;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{read_to_string, OpenOptions};
+use std::io::Write;
+
+// The design of the couverts: where the logo, the names, the groups, the address and the badges
+// go, in which font, size and colour. Without a layout.yaml, the couverts look like they always
+// did. Other Abteilungen can change the design there without touching the code.
+//
+// Positions are in mm, measured from a corner of the couvert, so that the same layout.yaml works
+// for different envelope formats. The address is placed where the envelope format wants it unless
+// layout.yaml gives it a position.
+
+pub const LAYOUT_YAML_FILE: &str = "layout.yaml";
+const LAYOUT_YAML_FILE_TEMPLATE: &str = r###"# Das Aussehen der Couverts.
+# Positionen sind in mm, gemessen von der Ecke `from` (bottom_left, top_left, top_right oder
+# bottom_right) zur unteren linken Ecke des Elements. Schriften sind regular oder light, Farben
+# sind CMYK-Werte zwischen 0 und 1, z.B. [0, 0, 0, 1] für schwarz.
+# Wird diese Datei gelöscht, wird sie mit dem ursprünglichen Aussehen neu erstellt.
+logo:
+    show: true
+    position: { x: 12, y: 28, from: top_left }
+    scale: 1.2
+# Ohne position steht die Adresse dort, wo das Couvertformat es vorsieht.
+address:
+    font: light
+    size: 18
+    color: [0, 0, 0, 1]
+# Die (Cevi-)Namen der Personen im Couvert
+names:
+    show: true
+    position: { x: 32, y: 18, from: top_left }
+    font: regular
+    size: 11
+    color: [0, 0, 0, 1]
+# Die Gruppen der Personen im Couvert
+groups:
+    show: true
+    position: { x: 32, y: 23, from: top_left }
+    font: regular
+    size: 11
+    color: [0, 0, 0, 1]
+# Die Anzahl Personen pro Rolle. position ist die des untersten Badges.
+badges:
+    show: true
+    position: { x: 12, y: 12, from: bottom_left }
+    spacing: 15
+    width: 30
+    height: 10
+    font: regular
+    size: 11
+    fill_color: [0, 0, 0, 1]
+    text_color: [0, 0, 0, 0]
+# Text, der auf jedes Couvert gedruckt wird, z.B.
+#free_text:
+#    - text: "Nicht nachsenden"
+#      position: { x: 12, y: 5, from: top_left }
+#      size: 9
+free_text: []
+# Eine persönliche Zeile pro Couvert. Platzhalter: {nicknames}, {groups}, {roles}, {count} und
+# {name} (die erste Adresszeile). Passt eine Gruppe unter by_group zu einer Person im Couvert, wird
+# diese Zeile gedruckt, sonst eine passende Rolle unter by_role, sonst default. Ohne default
+# erhalten die übrigen Couverts keine Zeile.
+#message:
+#    position: { x: 32, y: 30, from: top_left }
+#    size: 11
+#    default: "Infos zum Sommerlager für {groups}"
+#    by_role:
+#        Leiter: "Leiterinfos für {nicknames}"
+#    by_group:
+#        Holon: "Holon-Lager: Infos für {nicknames}"
+# Eigene Schriften (TTF oder OTF) für die Adresse, für die Namen, Gruppen und Texte oben, und für
+# die Badges. Ohne Angabe wird die mitgelieferte Schrift DejaVu Sans verwendet, regular bzw. light
+# wie oben gewählt. Eine eigene Schrift ersetzt beide.
+#fonts:
+#    address: "C:/Windows/Fonts/calibril.ttf"
+#    names: "C:/Windows/Fonts/calibri.ttf"
+#    badges: "C:/Windows/Fonts/calibri.ttf"
+fonts: {}
+"###;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Corner {
+    BottomLeft,
+    TopLeft,
+    TopRight,
+    BottomRight,
+}
+
+impl Default for Corner {
+    fn default() -> Self {
+        Corner::BottomLeft
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Position {
+    pub x: f64,
+    pub y: f64,
+    #[serde(default)]
+    pub from: Corner,
+}
+
+impl Position {
+    fn new(x: f64, y: f64, from: Corner) -> Self {
+        Position {
+            x: x,
+            y: y,
+            from: from,
+        }
+    }
+
+    /// the position in mm from the lower left corner of a page of the given size
+    pub fn resolve(&self, page_width: f64, page_height: f64) -> (f64, f64) {
+        match self.from {
+            Corner::BottomLeft => (self.x, self.y),
+            Corner::TopLeft => (self.x, page_height - self.y),
+            Corner::TopRight => (page_width - self.x, page_height - self.y),
+            Corner::BottomRight => (page_width - self.x, self.y),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum FontStyle {
+    Regular,
+    Light,
+}
+
+impl Default for FontStyle {
+    fn default() -> Self {
+        FontStyle::Regular
+    }
+}
+
+/// CMYK, each between 0 and 1
+pub type CmykColor = [f64; 4];
+
+const BLACK: CmykColor = [0.0, 0.0, 0.0, 1.0];
+const WHITE: CmykColor = [0.0, 0.0, 0.0, 0.0];
+
+fn black() -> CmykColor {
+    BLACK
+}
+
+fn white() -> CmykColor {
+    WHITE
+}
+
+fn yes() -> bool {
+    true
+}
+
+fn default_font_size() -> i64 {
+    11
+}
+
+pub fn to_pdf_color(color: &CmykColor) -> printpdf::Color {
+    printpdf::Color::Cmyk(printpdf::Cmyk::new(
+        color[0], color[1], color[2], color[3], None,
+    ))
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Layout {
+    pub logo: LogoField,
+    pub address: AddressField,
+    pub names: TextField,
+    pub groups: TextField,
+    pub badges: BadgeField,
+    pub free_text: Vec<FreeText>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<MessageField>,
+    pub fonts: FontPaths,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct LogoField {
+    #[serde(default = "yes")]
+    pub show: bool,
+    /// of the lower left corner of the logo
+    pub position: Position,
+    /// 1.0 is 150 dpi
+    pub scale: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct AddressField {
+    /// of the first line. `None` means where the envelope format wants it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position: Option<Position>,
+    #[serde(default)]
+    pub font: FontStyle,
+    #[serde(default = "default_font_size")]
+    pub size: i64,
+    #[serde(default = "black")]
+    pub color: CmykColor,
+}
+
+/// a single line of text, e.g. the names or the groups
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct TextField {
+    #[serde(default = "yes")]
+    pub show: bool,
+    pub position: Position,
+    #[serde(default)]
+    pub font: FontStyle,
+    #[serde(default = "default_font_size")]
+    pub size: i64,
+    #[serde(default = "black")]
+    pub color: CmykColor,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct BadgeField {
+    #[serde(default = "yes")]
+    pub show: bool,
+    /// of the lowest badge
+    pub position: Position,
+    /// from one badge to the next, upwards
+    pub spacing: f64,
+    pub width: f64,
+    pub height: f64,
+    #[serde(default)]
+    pub font: FontStyle,
+    #[serde(default = "default_font_size")]
+    pub size: i64,
+    #[serde(default = "black")]
+    pub fill_color: CmykColor,
+    #[serde(default = "white")]
+    pub text_color: CmykColor,
+}
+
+/// the same text on every couvert
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct FreeText {
+    pub text: String,
+    pub position: Position,
+    #[serde(default)]
+    pub font: FontStyle,
+    #[serde(default = "default_font_size")]
+    pub size: i64,
+    #[serde(default = "black")]
+    pub color: CmykColor,
+}
+
+/// a line with placeholders, chosen by the groups and roles of the receivers
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct MessageField {
+    pub position: Position,
+    #[serde(default)]
+    pub font: FontStyle,
+    #[serde(default = "default_font_size")]
+    pub size: i64,
+    #[serde(default = "black")]
+    pub color: CmykColor,
+    /// for couverts without a matching group or role. No line if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<MessageTemplate>,
+    /// by the role as it is printed on the badges, e.g. "Leiter"
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub by_role: BTreeMap<String, MessageTemplate>,
+    /// by the group name as it is printed on the couvert
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub by_group: BTreeMap<String, MessageTemplate>,
+}
+
+impl MessageField {
+    /// The first group of a receiver that has a line wins, then the first role, then the default.
+    /// Groups come first because they are more specific than roles.
+    pub fn template_for(&self, couvert: &CouvertInfo) -> Option<&MessageTemplate> {
+        couvert
+            .receivers
+            .iter()
+            .find_map(|receiver| self.by_group.get(&receiver.group))
+            .or_else(|| {
+                couvert
+                    .receivers
+                    .iter()
+                    .find_map(|receiver| self.by_role.get(&receiver.role.value()))
+            })
+            .or(self.default.as_ref())
+    }
+}
+
+impl Default for Layout {
+    /// the design the couverts have always had
+    fn default() -> Self {
+        Layout {
+            logo: LogoField {
+                show: true,
+                position: Position::new(12.0, 28.0, Corner::TopLeft),
+                scale: 8.0 * 0.15,
+            },
+            address: AddressField {
+                position: None,
+                font: FontStyle::Light,
+                size: 18,
+                color: BLACK,
+            },
+            names: TextField {
+                show: true,
+                position: Position::new(32.0, 18.0, Corner::TopLeft),
+                font: FontStyle::Regular,
+                size: 11,
+                color: BLACK,
+            },
+            groups: TextField {
+                show: true,
+                position: Position::new(32.0, 23.0, Corner::TopLeft),
+                font: FontStyle::Regular,
+                size: 11,
+                color: BLACK,
+            },
+            badges: BadgeField {
+                show: true,
+                position: Position::new(12.0, 12.0, Corner::BottomLeft),
+                spacing: 15.0,
+                width: 30.0,
+                height: 10.0,
+                font: FontStyle::Regular,
+                size: 11,
+                fill_color: BLACK,
+                text_color: WHITE,
+            },
+            free_text: Vec::new(),
+            message: None,
+            fonts: FontPaths::default(),
+        }
+    }
+}
+
+/// Reads layout.yaml, or creates it with the default layout if there is none. Any problem is
+/// shown to the user and the default layout is used instead.
+pub fn load_layout(user_interface: &dyn ui::UserInteractor) -> Layout {
+    match read_to_string(LAYOUT_YAML_FILE) {
+        Ok(yaml) => match serde_yaml::from_str::<Layout>(&yaml) {
+            Ok(layout) => layout,
+            Err(e) => {
+                user_interface.error_loading_layout(&e);
+                Layout::default()
+            },
+        },
+        Err(_e) => {
+            println!("pdfgen: no {} yet. Creating it...", LAYOUT_YAML_FILE);
+            if let Err(e) = create_layout_yaml_file_template() {
+                user_interface.error_loading_layout(&e);
+            }
+            Layout::default()
+        },
+    }
+}
+
+pub fn create_layout_yaml_file_template() -> Result<(), std::io::Error> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(LAYOUT_YAML_FILE)?;
+    file.write_all(LAYOUT_YAML_FILE_TEMPLATE.as_bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Corner, FontStyle, Layout, Position};
+
+    #[test]
+    fn test_template_is_the_default_layout() {
+        let layout: Layout = serde_yaml::from_str(super::LAYOUT_YAML_FILE_TEMPLATE).unwrap();
+        assert_eq!(layout, Layout::default());
+    }
+
+    #[test]
+    fn test_missing_sections_keep_the_default() {
+        let yaml = r###"
+names:
+    position: { x: 40, y: 20, from: top_left }
+    size: 14
+free_text:
+    - text: "Nicht nachsenden"
+      position: { x: 12, y: 5 }
+"###;
+        let layout: Layout = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(layout.names.size, 14);
+        assert_eq!(layout.names.font, FontStyle::Regular);
+        assert!(layout.names.show);
+        assert_eq!(layout.groups, Layout::default().groups);
+        assert_eq!(layout.free_text[0].position.from, Corner::BottomLeft);
+
+        assert!(serde_yaml::from_str::<Layout>(
+            "names: { position: { x: 1, y: 1 }, colour: [0, 0, 0, 1] }"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_message_by_group_then_role() {
+        let yaml = r###"
+message:
+    position: { x: 32, y: 30, from: top_left }
+    default: "Infos für {groups}"
+    by_role:
+        Leiter: "Leiterinfos für {nicknames}"
+    by_group:
+        Holon: "Holon-Lager"
+"###;
+        let message = serde_yaml::from_str::<Layout>(yaml)
+            .unwrap()
+            .message
+            .unwrap();
+        let receiver = |nickname: &str, group: &str, role: crate::Role| crate::Receiver {
+            nickname: String::from(nickname),
+            group: String::from(group),
+            role: role,
+        };
+        let mut couvert = crate::CouvertInfo {
+            receivers: vec![
+                receiver("Pseudo", "Skapande", crate::Role::Teilnehmer),
+                receiver("Focus", "Skapande", crate::Role::Leiter),
+            ],
+            address: vec![String::from("Familie Muster")],
+            country: None,
+        };
+        let render = |couvert: &crate::CouvertInfo| {
+            message
+                .template_for(couvert)
+                .map(|template| template.render(couvert))
+        };
+        assert_eq!(
+            render(&couvert),
+            Some(String::from("Leiterinfos für Pseudo und Focus"))
+        );
+        couvert
+            .receivers
+            .push(receiver("Levanzo", "Holon", crate::Role::Teilnehmer));
+        assert_eq!(render(&couvert), Some(String::from("Holon-Lager")));
+        couvert.receivers = vec![receiver("Pseudo", "Skapande", crate::Role::Teilnehmer)];
+        assert_eq!(render(&couvert), Some(String::from("Infos für Skapande")));
+
+        assert!(serde_yaml::from_str::<Layout>(
+            "message: { position: { x: 1, y: 1 }, default: \"Hallo {vorname}\" }"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_resolve_position() {
+        assert_eq!(
+            Position::new(12.0, 28.0, Corner::TopLeft).resolve(229.0, 162.0),
+            (12.0, 134.0)
+        );
+        assert_eq!(
+            Position::new(10.0, 5.0, Corner::BottomRight).resolve(229.0, 162.0),
+            (219.0, 5.0)
+        );
+    }
+}
@@ -0,0 +1,275 @@
+use crate::fitting;
+use crate::fonts::{FontRole, Fonts, PdfFonts};
+use crate::layout::{FontStyle, Layout};
+use crate::placeholders::MessageTemplate;
+use crate::CouvertInfo;
+
+// A4 letters for window couverts (C5, C6/5), one per couvert, with the address where SN 010130
+// puts the address field of a window on the left or on the right. The letter body is a text file
+// with a little Markdown: `# ` starts a heading, `- ` a bullet point, and empty lines separate
+// paragraphs. Placeholders like {nicknames} are filled in for every letter.
+
+const A4_WIDTH: f64 = 210.0;
+const A4_HEIGHT: f64 = 297.0;
+const MM_PER_PT: f64 = 25.4 / 72.0;
+/// the address field is 45 mm high, the body starts a bit below it
+const ADDRESS_FIELD_TOP: f64 = 60.0;
+const ADDRESS_FIELD_WIDTH: f64 = 85.0;
+const BODY_TOP: f64 = 110.0;
+const BODY_BOTTOM: f64 = 20.0;
+const MARGIN_LEFT: f64 = 22.0;
+const MARGIN_RIGHT: f64 = 20.0;
+const ADDRESS_FONT_SIZE: i64 = 12;
+const BODY_FONT_SIZE: i64 = 11;
+const HEADING_FONT_SIZE: i64 = 14;
+const LINE_SPACING: f64 = 1.3;
+
+/// where the address window of the couvert is, seen from the front
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WindowPosition {
+    Left,
+    Right,
+}
+
+impl WindowPosition {
+    /// the left edge of the address field in mm from the left edge of the sheet
+    fn address_x(&self) -> f64 {
+        match self {
+            WindowPosition::Left => 22.0,
+            WindowPosition::Right => 118.0,
+        }
+    }
+}
+
+impl std::str::FromStr for WindowPosition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "left" | "links" => Ok(WindowPosition::Left),
+            "right" | "rechts" => Ok(WindowPosition::Right),
+            other => Err(format!(
+                "unknown window position {:?}, expected left or right",
+                other
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Block {
+    Heading(MessageTemplate),
+    Paragraph(MessageTemplate),
+    Bullet(MessageTemplate),
+}
+
+/// the body of the letters
+#[derive(Debug, Clone, PartialEq)]
+pub struct LetterTemplate {
+    blocks: Vec<Block>,
+}
+
+impl std::str::FromStr for LetterTemplate {
+    type Err = String;
+
+    /// errors name the line, counted from 1
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut blocks: Vec<Block> = Vec::new();
+        // the lines of the paragraph that is not finished yet, joined by spaces
+        let mut paragraph: Option<(usize, String)> = None;
+        let parse = |line_number: usize, text: &str| {
+            text.parse::<MessageTemplate>()
+                .map_err(|e| format!("line {}: {}", line_number, e))
+        };
+
+        for (index, line) in s.lines().enumerate() {
+            let line_number = index + 1;
+            let trimmed = line.trim();
+            let block_start =
+                trimmed.is_empty() || trimmed.starts_with("# ") || trimmed.starts_with("- ");
+            if block_start {
+                if let Some((first_line, text)) = paragraph.take() {
+                    blocks.push(Block::Paragraph(parse(first_line, &text)?));
+                }
+            }
+            if trimmed.starts_with("# ") {
+                blocks.push(Block::Heading(parse(line_number, trimmed[2..].trim())?));
+            } else if trimmed.starts_with("- ") {
+                blocks.push(Block::Bullet(parse(line_number, trimmed[2..].trim())?));
+            } else if !trimmed.is_empty() {
+                match paragraph.as_mut() {
+                    Some((_first_line, text)) => {
+                        text.push(' ');
+                        text.push_str(trimmed);
+                    },
+                    None => paragraph = Some((line_number, String::from(trimmed))),
+                }
+            }
+        }
+        if let Some((first_line, text)) = paragraph.take() {
+            blocks.push(Block::Paragraph(parse(first_line, &text)?));
+        }
+        Ok(LetterTemplate { blocks: blocks })
+    }
+}
+
+/// One A4 letter per couvert, with the address in the address field of `window` and `body` below
+/// it, continued on further pages if it is too long. Without `body`, the letters only have the
+/// address, e.g. to put in front of a printed letter.
+pub fn generate_letters(
+    couverts: &Vec<CouvertInfo>,
+    user_interface: Option<&dyn ui::UserInteractor>,
+    window: WindowPosition,
+    body: Option<&LetterTemplate>,
+    layout: &Layout,
+    fonts: &Fonts,
+) -> printpdf::PdfDocumentReference {
+    use printpdf::*;
+
+    let page_width = Mm(A4_WIDTH);
+    let page_height = Mm(A4_HEIGHT);
+    let (doc, first_page, first_layer) =
+        PdfDocument::new("Briefe", page_width, page_height, "Layer 1");
+    let pdf_fonts = PdfFonts::add_to(&doc, fonts);
+    let address_font = pdf_fonts.get(FontRole::Address, FontStyle::Light);
+    let body_font = pdf_fonts.get(FontRole::Names, FontStyle::Regular);
+    let address_font_file = fonts.get(FontRole::Address, FontStyle::Light);
+    let body_font_file = fonts.get(FontRole::Names, FontStyle::Regular);
+
+    let line_height = |font_size: i64| font_size as f64 * MM_PER_PT * LINE_SPACING;
+
+    let mut next_layer = Some(doc.get_page(first_page).get_layer(first_layer));
+    let mut new_page = |name: String| -> PdfLayerReference {
+        match next_layer.take() {
+            Some(layer) => layer,
+            None => {
+                let (page, layer) = doc.add_page(page_width, page_height, name);
+                doc.get_page(page).get_layer(layer)
+            },
+        }
+    };
+
+    for (num, couvert) in couverts.iter().enumerate() {
+        let current_layer = new_page(format!("Brief {}, Layer 1", num));
+
+        if layout.logo.show {
+            let (logo_x, logo_y) = layout.logo.position.resolve(A4_WIDTH, A4_HEIGHT);
+            crate::add_bitmap_to_layer(
+                &current_layer,
+                Some(Mm(logo_x)),
+                Some(Mm(logo_y)),
+                Some(layout.logo.scale),
+                Some(layout.logo.scale),
+                &crate::LOGO_BMP_BYTES,
+            );
+        }
+
+        let address = fitting::fit_and_report(
+            user_interface,
+            address_font_file,
+            &couvert
+                .address
+                .iter()
+                .chain(couvert.country.iter())
+                .cloned()
+                .collect::<Vec<String>>(),
+            ADDRESS_FONT_SIZE,
+            ADDRESS_FIELD_WIDTH,
+        );
+
+        // the text cursor is the baseline of the first line
+        current_layer.begin_text_section();
+        current_layer.set_font(address_font, address.size);
+        current_layer.set_text_cursor(
+            Mm(window.address_x()),
+            Mm(A4_HEIGHT - ADDRESS_FIELD_TOP - address.size as f64 * MM_PER_PT),
+        );
+        current_layer.set_line_height(address.size);
+        for line in address.lines {
+            current_layer.write_text(line, address_font);
+            current_layer.add_line_break();
+        }
+        current_layer.end_text_section();
+
+        let body = match body {
+            Some(body) => body,
+            None => continue,
+        };
+        let mut current_layer = current_layer;
+        let mut y = A4_HEIGHT - BODY_TOP;
+        let mut continuation = 0;
+        for block in body.blocks.iter() {
+            let (template, font_size, indent, bullet) = match block {
+                Block::Heading(template) => (template, HEADING_FONT_SIZE, 0.0, false),
+                Block::Paragraph(template) => (template, BODY_FONT_SIZE, 0.0, false),
+                Block::Bullet(template) => (template, BODY_FONT_SIZE, 5.0, true),
+            };
+            let lines = fitting::wrap(
+                body_font_file,
+                &template.render(couvert),
+                font_size,
+                A4_WIDTH - MARGIN_LEFT - MARGIN_RIGHT - indent,
+            );
+            for (index, line) in lines.into_iter().enumerate() {
+                if y - line_height(font_size) < BODY_BOTTOM {
+                    continuation += 1;
+                    current_layer = new_page(format!(
+                        "Brief {}, Seite {}, Layer 1",
+                        num,
+                        continuation + 1
+                    ));
+                    y = A4_HEIGHT - BODY_BOTTOM;
+                }
+                y -= line_height(font_size);
+                if bullet && index == 0 {
+                    current_layer.use_text("•", font_size, Mm(MARGIN_LEFT), Mm(y), body_font);
+                }
+                current_layer.use_text(
+                    line,
+                    font_size,
+                    Mm(MARGIN_LEFT + indent),
+                    Mm(y),
+                    body_font,
+                );
+            }
+            // a blank line between blocks
+            y -= line_height(BODY_FONT_SIZE) / 2.0;
+        }
+    }
+
+    if let Some(ui) = user_interface {
+        ui.on_pdf_generation_finished();
+    }
+
+    return doc;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Block, LetterTemplate};
+
+    #[test]
+    fn test_parse_letter_template() {
+        let template: LetterTemplate = concat!(
+            "# Sommerlager\n\n",
+            "Hallo {nicknames}\nwir gehen\nins Lager.\n",
+            "- Schlafsack\n- Zahnbürste\n\n",
+            "Bis bald"
+        )
+        .parse()
+        .unwrap();
+        assert_eq!(template.blocks.len(), 5);
+        match &template.blocks[1] {
+            Block::Paragraph(paragraph) => assert_eq!(
+                paragraph,
+                &"Hallo {nicknames} wir gehen ins Lager.".parse().unwrap()
+            ),
+            other => panic!("{:?}", other),
+        }
+
+        let error = "Hallo\n\n- {vorname}"
+            .parse::<LetterTemplate>()
+            .unwrap_err();
+        assert!(error.starts_with("line 3:"), "{}", error);
+    }
+}
@@ -2,13 +2,24 @@ use chrono;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::env;
+pub mod envelope;
+pub mod fitting;
+pub mod fonts;
+pub mod labels;
+pub mod layout;
+pub mod letter;
+pub mod placeholders;
+pub use envelope::{EnvelopeConfig, EnvelopeFormat};
+pub use fonts::Fonts;
+pub use labels::{generate_labels, LabelFormat, LabelSheet};
+pub use letter::{generate_letters, LetterTemplate, WindowPosition};
+pub use layout::Layout;
 
-const CALIBRI_FONT: &'static [u8] = include_bytes!("../res/fonts/calibri.ttf");
-const CALIBRI_LIGHT_FONT: &'static [u8] = include_bytes!("../res/fonts/calibriL.ttf");
 const LOGO_BMP_BYTES: &'static [u8] = include_bytes!("../res/images/logo.bmp");
 const TOOL_LOGO_PNG_BYTES: &'static [u8] = include_bytes!("../res/images/icon.bmp");
 
 const VERYBOSE: bool = false;
+const MM_PER_PT: f64 = 25.4 / 72.0;
 
 pub fn main() {
     println!("Hello, world from {}!", file!());
@@ -44,9 +55,10 @@ pub fn main() {
     let mut couverts: Vec<CouvertInfo> = vec![CouvertInfo {
         receivers: receivers,
         address: vec_str_to_vec_string(&address),
+        country: None,
     }];
 
-    let doc_generated: printpdf::PdfDocumentReference = generate_couverts(&mut couverts, None, true, true, true);
+    let doc_generated: printpdf::PdfDocumentReference = generate_couverts(&mut couverts, None, "Sortierung der Couverts nach der Gruppe der ersten Person im Couvert.", GroupSeparator::Nothing, EnvelopeFormat::C5, &Layout::default(), &Fonts::default(), true, true, true);
     let mut buf = std::io::BufWriter::new(std::fs::File::create(filename).expect("What?"));
     doc_generated.save(&mut buf).expect("The Fuck?");
 }
@@ -55,6 +67,61 @@ pub fn main() {
 pub struct CouvertInfo {
     pub receivers: Vec<Receiver>,
     pub address: Vec<String>,
+    /// printed as the last address line. `None` for couverts that stay in Switzerland.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub country: Option<String>,
+}
+
+/// What marks the place where the group of the first receiver changes, so that the folding team
+/// does not have to read every couvert. Only useful if the couverts are sorted by group.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupSeparator {
+    Nothing,
+    /// a page with the name of the group and the number of its couverts before its first couvert
+    Page,
+    /// a coloured triangle on the left edge, in a different colour for each group, and the name of
+    /// the group and the number of its couverts above it on the first couvert
+    EdgeMarker,
+}
+
+impl Default for GroupSeparator {
+    fn default() -> Self {
+        GroupSeparator::Nothing
+    }
+}
+
+impl std::str::FromStr for GroupSeparator {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "none" => Ok(GroupSeparator::Nothing),
+            "page" => Ok(GroupSeparator::Page),
+            "marker" => Ok(GroupSeparator::EdgeMarker),
+            other => Err(format!(
+                "unknown group separator {:?}, expected none, page or marker",
+                other
+            )),
+        }
+    }
+}
+
+/// the group of the first receiver of each run of couverts with the same such group, and the
+/// number of couverts in the run
+fn group_runs(couverts: &Vec<CouvertInfo>) -> Vec<(String, usize)> {
+    let mut runs: Vec<(String, usize)> = Vec::new();
+    for couvert in couverts.iter() {
+        let group = couvert
+            .receivers
+            .first()
+            .map(|r| r.group.clone())
+            .unwrap_or_default();
+        match runs.last_mut() {
+            Some((run_group, count)) if *run_group == group => *count += 1,
+            _ => runs.push((group, 1)),
+        }
+    }
+    return runs;
 }
 
 pub fn vec_str_to_vec_string(v: &Vec<&str>) -> Vec<String> {
@@ -65,9 +132,17 @@ pub fn vec_str_to_vec_string(v: &Vec<&str>) -> Vec<String> {
     return vec;
 }
 
+/// `sorting_description` explains on the info page in which order the couverts are sorted.
+/// `envelope_format` decides the page size and where the address goes, `layout` everything else on
+/// the couverts, in `fonts`. The `print_*` flags can only hide what the layout shows.
 pub fn generate_couverts(
     couverts: &mut Vec<CouvertInfo>,
     user_interface: Option<&dyn ui::UserInteractor>,
+    sorting_description: &str,
+    group_separator: GroupSeparator,
+    envelope_format: EnvelopeFormat,
+    layout: &Layout,
+    fonts: &Fonts,
     print_sidebadges: bool,
     print_top_groups: bool,
     print_top_names: bool,
@@ -76,21 +151,24 @@ pub fn generate_couverts(
 
     // document config
     let document_title = "Versand";
-    let address_font_size = 18;
     let debug_font_size = 12;
     let sorting_font_size = 18;
     let donation_font_size = 13;
     let names_font_size = 11;
-    let badge_text_font_size = 11;
-    let page_width = Mm(229.0);
-    let page_height = Mm(162.0);
-    let address_offset_x = Mm(120.0);
-    let address_offset_y = Mm(65.0);
+    let envelope = envelope_format.dimensions();
+    let page_width = Mm(envelope.width);
+    let page_height = Mm(envelope.height);
+    let (address_offset_x, address_offset_y) = match layout.address.position {
+        Some(position) => position.resolve(envelope.width, envelope.height),
+        None => (envelope.address_x, envelope.address_y),
+    };
     let border_wh = Mm(12.0);
-    let names_offset_x = border_wh + Mm(20.0);
-    let names_offset_y = page_height - Mm(18.0);
     let debug_offset_x = border_wh + Mm(20.0);
     let debug_offset_y = page_height - Mm(18.0);
+    let separator_font_size = 36;
+    let marker_size = Mm(12.0);
+    // the group name next to the marker must not run into the address of small couverts
+    let marker_label_width = 50.0;
 
     // create the document
     let (doc, info_page, info_layer): (
@@ -104,16 +182,34 @@ pub fn generate_couverts(
             /*initial_layer_name*/ "Layer 1",
             );
 
-    // load a font
-    let mut font_reader = std::io::Cursor::new(CALIBRI_FONT.as_ref());
-    let font_calibri = doc
-        .add_external_font(&mut font_reader)
-        .expect("Failed to load font");
-    // load a second font
-    let mut font_reader_light = std::io::Cursor::new(CALIBRI_LIGHT_FONT.as_ref());
-    let font_calibri_light = doc
-        .add_external_font(&mut font_reader_light)
-        .expect("Failed to load font");
+    let pdf_fonts = fonts::PdfFonts::add_to(&doc, fonts);
+    let font_regular = &pdf_fonts.regular;
+    let font_of = |role: fonts::FontRole, style: layout::FontStyle| pdf_fonts.get(role, style);
+    // positions in the layout are measured from any corner
+    let at = |position: &layout::Position| -> (Mm, Mm) {
+        let (x, y) = position.resolve(envelope.width, envelope.height);
+        (Mm(x), Mm(y))
+    };
+    // texts must not run over the right border of the couvert, nor into the franking zone
+    let space_right_of = |x: Mm, y: Mm, size: i64| -> f64 {
+        envelope.space_right_of(x.0, y.0 + size as f64 * MM_PER_PT)
+    };
+    // free texts are the same on every couvert, so they are fitted and reported only once
+    let free_texts: Vec<(Mm, Mm, fitting::Fitted)> = layout
+        .free_text
+        .iter()
+        .map(|free_text| {
+            let (text_x, text_y) = at(&free_text.position);
+            let fitted = fitting::fit_and_report(
+                user_interface,
+                fonts.get(fonts::FontRole::Names, free_text.font),
+                &[free_text.text.clone()],
+                free_text.size,
+                space_right_of(text_x, text_y, free_text.size),
+            );
+            (text_x, text_y, fitted)
+        })
+        .collect();
 
     // draw logo on first page
     let curr_info_page_layer = doc.get_page(info_page).get_layer(info_layer);
@@ -133,25 +229,24 @@ pub fn generate_couverts(
         debug_font_size,
         debug_offset_x,
         debug_offset_y,
-        &font_calibri,
+        font_regular,
         );
     // write sorting info
     let sorting_text = format!("Sortierung der (Cevi-)Namen im selben Couvert alphabetisch.");
-    let sorting_text_2 =
-        format!("Sortierung der Couverts nach der Gruppe der ersten Person im Couvert.");
+    let sorting_text_2 = sorting_description;
     curr_info_page_layer.use_text(
         sorting_text,
         sorting_font_size,
         debug_offset_x,
         debug_offset_y - Mm(16.0),
-        &font_calibri,
+        font_regular,
         );
     curr_info_page_layer.use_text(
         sorting_text_2,
         sorting_font_size,
         debug_offset_x,
         debug_offset_y - Mm(16.0 + 10.0),
-        &font_calibri,
+        font_regular,
         );
 
     // write donation text
@@ -163,7 +258,7 @@ pub fn generate_couverts(
             donation_font_size,
             debug_offset_x,
             debug_offset_y - Mm(40.0 + i as f64),
-            &font_calibri,
+            font_regular,
             );
         i += 8;
     }
@@ -176,20 +271,59 @@ pub fn generate_couverts(
         donation_font_size,
         Mm(5.),
         Mm(5.),
-        &font_calibri,
+        font_regular,
         );
     let made_by_text2 =
         format!("eric@mink.li");
     curr_info_page_layer.use_text(
         made_by_text2,
         donation_font_size,
-        Mm(200.),
+        page_width - Mm(29.),
         Mm(5.),
-        &font_calibri,
+        font_regular,
         );
 
 
+    // the couverts are numbered by the run of couverts with the same group they belong to
+    let runs = group_runs(couverts);
+    let run_of_couvert: Vec<usize> = runs
+        .iter()
+        .enumerate()
+        .flat_map(|(run, (_group, count))| std::iter::repeat(run).take(*count))
+        .collect();
+
     for (num, couvert) in couverts.iter_mut().enumerate() {
+        let run = run_of_couvert[num];
+        let starts_run = num == 0 || run_of_couvert[num - 1] != run;
+        let (run_group, run_count) = &runs[run];
+
+        if starts_run && group_separator == GroupSeparator::Page {
+            let (separator_page, separator_layer) = doc.add_page(
+                page_width,
+                page_height,
+                format!("Trennseite {}, Layer 1", run),
+            );
+            let separator_layer = doc.get_page(separator_page).get_layer(separator_layer);
+            separator_layer.use_text(
+                run_group.clone(),
+                separator_font_size,
+                debug_offset_x,
+                page_height / 2.0,
+                font_regular,
+            );
+            separator_layer.use_text(
+                format!(
+                    "{} Couvert{}",
+                    run_count,
+                    if *run_count == 1 { "" } else { "s" }
+                ),
+                sorting_font_size,
+                debug_offset_x,
+                page_height / 2.0 - Mm(14.0),
+                font_regular,
+            );
+        }
+
         // add new page
         if VERYBOSE {
             println!("Generating page {}", num);
@@ -201,37 +335,136 @@ pub fn generate_couverts(
         let current_page = doc.get_page(next_page);
         let current_layer = current_page.get_layer(layer1);
 
+        // halfway up the left edge, because the franking zone at the top right and the coding zone
+        // at the bottom must stay clear for the sorting machines of the Post
+        if group_separator == GroupSeparator::EdgeMarker {
+            let marker_y = page_height / 2.0;
+            draw_edge_marker(&current_layer, marker_y, marker_size, run);
+            if starts_run {
+                let label = fitting::fit_lines(
+                    &fonts.regular,
+                    &[format!(
+                        "{}: {} Couvert{}",
+                        run_group,
+                        run_count,
+                        if *run_count == 1 { "" } else { "s" }
+                    )],
+                    names_font_size,
+                    marker_label_width,
+                );
+                current_layer.use_text(
+                    label.lines[0].clone(),
+                    label.size,
+                    Mm(2.0),
+                    marker_y + marker_size / 2.0 + Mm(2.0),
+                    font_regular,
+                );
+            }
+        }
+
         // place the logo first, so that it is in the background
         // original logo_big is at 300 dpi approx 16/0.15 = 106mm
         // smaller logo is smaller by factor 8 (150x150px), so scaling should be factor 8 larger
-        add_bitmap_to_layer(
-            &current_layer,
-            Some(border_wh),
-            Some(page_height - Mm(16.0) - border_wh),
-            /*scaling x:*/ Some(8.0 * 0.15),
-            /*scaling y:*/ Some(8.0 * 0.15),
-            &LOGO_BMP_BYTES
-            );
+        if layout.logo.show {
+            let (logo_x, logo_y) = at(&layout.logo.position);
+            add_bitmap_to_layer(
+                &current_layer,
+                Some(logo_x),
+                Some(logo_y),
+                /*scaling x:*/ Some(layout.logo.scale),
+                /*scaling y:*/ Some(layout.logo.scale),
+                &LOGO_BMP_BYTES
+                );
+        }
 
         // draw names
+        let (names_x, names_y) = at(&layout.names.position);
+        draw_names(
+            &current_layer,
+            user_interface,
+            &layout.names,
+            fonts.get(fonts::FontRole::Names, layout.names.font),
+            font_of(fonts::FontRole::Names, layout.names.font),
+            (names_x, names_y),
+            space_right_of(names_x, names_y, layout.names.size),
+            print_top_names && layout.names.show,
+            couvert
+            .receivers
+            .iter()
+            .map(|r: &Receiver| &r.nickname as &str),
+            );
+        let (groups_x, groups_y) = at(&layout.groups.position);
         draw_names(
             &current_layer,
-            &font_calibri,
-            names_font_size,
-            (names_offset_x, names_offset_y),
-            print_top_groups, print_top_names,
+            user_interface,
+            &layout.groups,
+            fonts.get(fonts::FontRole::Names, layout.groups.font),
+            font_of(fonts::FontRole::Names, layout.groups.font),
+            (groups_x, groups_y),
+            space_right_of(groups_x, groups_y, layout.groups.size),
+            print_top_groups && layout.groups.show,
             couvert
             .receivers
             .iter()
-            .map(|r: &Receiver| (&r.nickname as &str, &r.group as &str)),
+            .map(|r: &Receiver| &r.group as &str),
+            );
+
+        for (free_text, (text_x, text_y, fitted)) in layout.free_text.iter().zip(free_texts.iter()) {
+            current_layer.set_fill_color(layout::to_pdf_color(&free_text.color));
+            current_layer.use_text(
+                fitted.lines[0].clone(),
+                fitted.size,
+                *text_x,
+                *text_y,
+                font_of(fonts::FontRole::Names, free_text.font),
             );
+        }
+
+        if let Some(message) = &layout.message {
+            if let Some(template) = message.template_for(couvert) {
+                let (message_x, message_y) = at(&message.position);
+                let fitted = fitting::fit_and_report(
+                    user_interface,
+                    fonts.get(fonts::FontRole::Names, message.font),
+                    &[template.render(couvert)],
+                    message.size,
+                    space_right_of(message_x, message_y, message.size),
+                );
+                current_layer.set_fill_color(layout::to_pdf_color(&message.color));
+                current_layer.use_text(
+                    fitted.lines[0].clone(),
+                    fitted.size,
+                    message_x,
+                    message_y,
+                    font_of(fonts::FontRole::Names, message.font),
+                );
+            }
+        }
 
         // position sample address
         {
-            let font_addresses = font_calibri_light.clone();
+            let font_addresses = font_of(fonts::FontRole::Address, layout.address.font).clone();
+            let address = fitting::fit_and_report(
+                user_interface,
+                fonts.get(fonts::FontRole::Address, layout.address.font),
+                &couvert
+                    .address
+                    .iter()
+                    .chain(couvert.country.iter())
+                    .cloned()
+                    .collect::<Vec<String>>(),
+                layout.address.size,
+                space_right_of(
+                    Mm(address_offset_x),
+                    Mm(address_offset_y),
+                    layout.address.size,
+                ),
+            );
+            let address_font_size = address.size;
+            current_layer.set_fill_color(layout::to_pdf_color(&layout.address.color));
             current_layer.begin_text_section();
             current_layer.set_font(&font_addresses, address_font_size);
-            current_layer.set_text_cursor(address_offset_x, address_offset_y);
+            current_layer.set_text_cursor(Mm(address_offset_x), Mm(address_offset_y));
             current_layer.set_line_height(address_font_size);
             current_layer.set_word_spacing(3000);
             current_layer.set_character_spacing(0);
@@ -240,15 +473,15 @@ pub fn generate_couverts(
                 TextRenderingMode::Fill,
                 );
 
-            for line in &(couvert.address) {
-                current_layer.write_text(line.clone(), &font_addresses);
+            for line in address.lines {
+                current_layer.write_text(line, &font_addresses);
                 current_layer.add_line_break();
             }
 
             current_layer.end_text_section();
         }
 
-        if print_sidebadges {
+        if print_sidebadges && layout.badges.show {
             // numbers in sidebadge
             let rolecount_dict: HashMap<Role, usize> = couvert.receivers.iter().fold(
                 /*init:*/ HashMap::new(),
@@ -260,13 +493,13 @@ pub fn generate_couverts(
                 );
 
             // position sidebadge
-            let badge_spacing_y = Mm(15.0);
             draw_sidebadges(
                 &current_layer,
-                &font_calibri,
-                badge_text_font_size,
-                (border_wh, border_wh),
-                badge_spacing_y,
+                user_interface,
+                &layout.badges,
+                fonts.get(fonts::FontRole::Badges, layout.badges.font),
+                font_of(fonts::FontRole::Badges, layout.badges.font),
+                at(&layout.badges.position),
                 rolecount_dict,
                 );
         }
@@ -279,76 +512,110 @@ pub fn generate_couverts(
     return doc;
 }
 
+/// writes all `texts` of a couvert into a single line, made to fit into `max_width` mm. Leaves the
+/// fill color black.
 fn draw_names<'a>(
     current_layer: &printpdf::PdfLayerReference,
+    user_interface: Option<&dyn ui::UserInteractor>,
+    field: &layout::TextField,
+    font_file: &fonts::FontFile,
     font: &printpdf::IndirectFontRef,
-    font_size: i64,
     (start_x, start_y): (printpdf::Mm, printpdf::Mm),
-    print_groups: bool, print_names: bool,
-    names_and_groups: impl Iterator<Item = (&'a str, &'a str)> + Clone,
+    max_width: f64,
+    print: bool,
+    texts: impl Iterator<Item = &'a str>,
 ) {
-    let line_distance_y = printpdf::Mm(5.0);
-
-    let names_str = if print_names { names_and_groups
-        .clone()
-        .map(|(name, _group)| name)
-        .collect::<Vec<&str>>()
-        .join(", ")
-    } else {
-        "".to_string()
-    };
+    if !print {
+        return;
+    }
+    let line = texts.collect::<Vec<&str>>().join(", ");
+    let fitted = fitting::fit_and_report(user_interface, font_file, &[line], field.size, max_width);
 
-    let groups_str = if print_groups { names_and_groups
-        .map(|(_name, group)| group)
-        .collect::<Vec<&str>>()
-        .join(", ")
-    } else {
-        "".to_string()
-    };
+    current_layer.set_fill_color(layout::to_pdf_color(&field.color));
+    current_layer.use_text(fitted.lines[0].clone(), fitted.size, start_x, start_y, &font);
+    current_layer.set_fill_color(printpdf::Color::Cmyk(printpdf::Cmyk::new(0.0, 0.0, 0.0, 1.0, None)));
+}
 
-    // position the names
-    current_layer.use_text(names_str, font_size, start_x, start_y, &font);
+/// Draws a triangle against the left edge of the page, pointing inwards, centred at `centre_y`.
+/// Neighbouring runs of couverts get different colours. Leaves the fill color black.
+fn draw_edge_marker(
+    current_layer: &printpdf::PdfLayerReference,
+    centre_y: printpdf::Mm,
+    size: printpdf::Mm,
+    run: usize,
+) {
+    use printpdf::{Cmyk, Color, Line, Mm, Point};
 
-    // position the group names
-    current_layer.use_text(
-        groups_str,
-        font_size,
-        start_x,
-        start_y - line_distance_y,
-        &font,
-    );
+    let colors = [
+        Cmyk::new(1.0, 0.0, 0.0, 0.0, None), // cyan
+        Cmyk::new(0.0, 1.0, 0.0, 0.0, None), // magenta
+        Cmyk::new(0.0, 0.0, 1.0, 0.0, None), // yellow
+        Cmyk::new(0.7, 0.0, 1.0, 0.0, None), // green
+    ];
+    let triangle = Line {
+        points: vec![
+            (Point::new(Mm(0.0), centre_y - size / 2.0), false),
+            (Point::new(size, centre_y), false),
+            (Point::new(Mm(0.0), centre_y + size / 2.0), false),
+        ],
+        is_closed: true,
+        has_fill: true,
+        has_stroke: false,
+        is_clipping_path: false,
+    };
+    current_layer.set_fill_color(Color::Cmyk(colors[run % colors.len()].clone()));
+    current_layer.add_shape(triangle);
+    current_layer.set_fill_color(Color::Cmyk(Cmyk::new(0.0, 0.0, 0.0, 1.0, None)));
 }
 
 fn draw_sidebadges(
     current_layer: &printpdf::PdfLayerReference,
+    user_interface: Option<&dyn ui::UserInteractor>,
+    badges: &layout::BadgeField,
+    font_file: &fonts::FontFile,
     font: &printpdf::IndirectFontRef,
-    font_size: i64,
     (start_x, start_y): (printpdf::Mm, printpdf::Mm),
-    badge_spacing_y: printpdf::Mm,
     numbers: HashMap<Role, usize>,
 ) {
     let mut y = start_y;
     for (role, num) in numbers {
         let txt: String = role.value();
         let text = format!("{} {}", num, txt);
-        draw_sidebadge(&current_layer, start_x, y, &font, font_size, &text);
-        y += badge_spacing_y;
+        // the text starts 2.5 mm into the badge and must end before its dent
+        let fitted = fitting::fit_and_report(
+            user_interface,
+            font_file,
+            &[text],
+            badges.size,
+            badges.width * 0.9 - 3.0,
+        );
+        draw_sidebadge(
+            &current_layer,
+            badges,
+            start_x,
+            y,
+            &font,
+            &fitted.lines[0],
+            fitted.size,
+        );
+        y += printpdf::Mm(badges.spacing);
     }
 }
 
 /// overwrites the fill color of the current layer and draws a badge at (origin_x, origin_y)
 fn draw_sidebadge(
     current_layer: &printpdf::PdfLayerReference,
+    badges: &layout::BadgeField,
     origin_x: printpdf::Mm,
     origin_y: printpdf::Mm,
     font: &printpdf::IndirectFontRef,
-    font_size: i64,
     text: &str,
+    font_size: i64,
 ) {
     use printpdf::{Line, Mm, Point};
 
-    let badge_height = 10.0;
-    let badge_width = 30.0;
+    let badge_height = badges.height;
+    let badge_width = badges.width;
     let badge_dent_width = badge_width / 10.0;
 
     // point relative to lower left corner (pos_x, pos_y)
@@ -383,14 +650,11 @@ fn draw_sidebadge(
     };
 
     // draw
-    let fill_color_black = printpdf::Color::Cmyk(printpdf::Cmyk::new(0.0, 0.0, 0.0, 1.0, None));
-    //let fill_color_blue = printpdf::Color::Cmyk(printpdf::Cmyk::new(0.0, 0.23, 0.56, 1.0, None));
-    current_layer.set_fill_color(fill_color_black);
+    current_layer.set_fill_color(layout::to_pdf_color(&badges.fill_color));
     current_layer.add_shape(line1);
 
     // create text
-    let fill_color_white = printpdf::Color::Cmyk(printpdf::Cmyk::new(0.0, 0.0, 0.0, 0.0, None));
-    current_layer.set_fill_color(fill_color_white);
+    current_layer.set_fill_color(layout::to_pdf_color(&badges.text_color));
     current_layer.use_text(
         text,
         font_size,
@@ -549,3 +813,45 @@ impl Role {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{CouvertInfo, Receiver, Role};
+
+    fn couvert(groups: &[&str]) -> CouvertInfo {
+        CouvertInfo {
+            receivers: groups
+                .iter()
+                .map(|group| Receiver {
+                    nickname: String::from("Levanzo"),
+                    group: String::from(*group),
+                    role: Role::Leiter,
+                })
+                .collect(),
+            address: vec![String::from("Familie Muster")],
+            country: None,
+        }
+    }
+
+    #[test]
+    fn test_group_runs() {
+        let couverts = vec![
+            couvert(&["Holon", "Senshi"]),
+            couvert(&["Holon"]),
+            couvert(&["Senshi", "Holon"]),
+            couvert(&["Holon"]),
+            couvert(&[]),
+        ];
+        // only the first receiver counts, and a group that comes back starts a new run
+        assert_eq!(
+            super::group_runs(&couverts),
+            vec![
+                (String::from("Holon"), 2),
+                (String::from("Senshi"), 1),
+                (String::from("Holon"), 1),
+                (String::new(), 1),
+            ]
+        );
+        assert!(super::group_runs(&Vec::new()).is_empty());
+    }
+}
@@ -0,0 +1,201 @@
+use crate::{CouvertInfo, Receiver};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+// Texts with placeholders that are filled in for every couvert, e.g. "Hallo {nicknames}". The
+// placeholders are checked when the text is read, so that a typo is reported once instead of
+// being printed on every couvert. `{{` and `}}` are literal braces.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Placeholder {
+    /// the (Cevi-)names of all receivers
+    Nicknames,
+    /// the groups of all receivers, each once
+    Groups,
+    /// the roles of all receivers, each once
+    Roles,
+    /// the number of receivers
+    Count,
+    /// the first line of the address, e.g. "Familie Muster"
+    Name,
+}
+
+impl Placeholder {
+    fn from_name(name: &str) -> Option<Self> {
+        match name.trim() {
+            "nickname" | "nicknames" => Some(Placeholder::Nicknames),
+            "group" | "groups" => Some(Placeholder::Groups),
+            "role" | "roles" => Some(Placeholder::Roles),
+            "count" => Some(Placeholder::Count),
+            "name" => Some(Placeholder::Name),
+            _ => None,
+        }
+    }
+
+    fn value(&self, couvert: &CouvertInfo) -> String {
+        match self {
+            Placeholder::Nicknames => join(couvert.receivers.iter().map(|r| r.nickname.clone())),
+            Placeholder::Groups => join_distinct(couvert.receivers.iter().map(|r| r.group.clone())),
+            Placeholder::Roles => {
+                join_distinct(couvert.receivers.iter().map(|r: &Receiver| r.role.value()))
+            },
+            Placeholder::Count => couvert.receivers.len().to_string(),
+            Placeholder::Name => couvert.address.first().cloned().unwrap_or_default(),
+        }
+    }
+}
+
+/// "Anna, Beat und Chris"
+fn join(parts: impl Iterator<Item = String>) -> String {
+    let parts: Vec<String> = parts.filter(|part| !part.is_empty()).collect();
+    match parts.split_last() {
+        None => String::new(),
+        Some((last, [])) => last.clone(),
+        Some((last, rest)) => format!("{} und {}", rest.join(", "), last),
+    }
+}
+
+fn join_distinct(parts: impl Iterator<Item = String>) -> String {
+    let mut distinct: Vec<String> = Vec::new();
+    for part in parts {
+        if !distinct.contains(&part) {
+            distinct.push(part);
+        }
+    }
+    join(distinct.into_iter())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Piece {
+    Text(String),
+    Placeholder(Placeholder),
+}
+
+/// Read from yaml as a plain string, and written back as the same string.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MessageTemplate {
+    source: String,
+    pieces: Vec<Piece>,
+}
+
+impl Serialize for MessageTemplate {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.source)
+    }
+}
+
+impl<'de> Deserialize<'de> for MessageTemplate {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let source = String::deserialize(deserializer)?;
+        source.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl std::str::FromStr for MessageTemplate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut pieces: Vec<Piece> = Vec::new();
+        let mut text = String::new();
+        let mut chars = s.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                },
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                },
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => return Err(format!("{{{} is not closed with }}", name)),
+                        }
+                    }
+                    let placeholder = Placeholder::from_name(&name).ok_or_else(|| {
+                        format!(
+                            "unknown placeholder {{{}}}, expected {}",
+                            name, "{nicknames}, {groups}, {roles}, {count} or {name}"
+                        )
+                    })?;
+                    if !text.is_empty() {
+                        pieces.push(Piece::Text(std::mem::replace(&mut text, String::new())));
+                    }
+                    pieces.push(Piece::Placeholder(placeholder));
+                },
+                '}' => return Err(String::from("single } without {, write }} for a brace")),
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            pieces.push(Piece::Text(text));
+        }
+        Ok(MessageTemplate {
+            source: String::from(s),
+            pieces: pieces,
+        })
+    }
+}
+
+impl MessageTemplate {
+    pub fn render(&self, couvert: &CouvertInfo) -> String {
+        self.pieces
+            .iter()
+            .map(|piece| match piece {
+                Piece::Text(text) => text.clone(),
+                Piece::Placeholder(placeholder) => placeholder.value(couvert),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MessageTemplate;
+    use crate::{CouvertInfo, Receiver, Role};
+
+    fn couvert() -> CouvertInfo {
+        let receiver = |nickname: &str, group: &str, role: Role| Receiver {
+            nickname: String::from(nickname),
+            group: String::from(group),
+            role: role,
+        };
+        CouvertInfo {
+            receivers: vec![
+                receiver("Focus", "Skapande", Role::Leiter),
+                receiver("Levanzo", "Holon", Role::Leiter),
+                receiver("Pseudo", "Holon", Role::Teilnehmer),
+            ],
+            address: vec![
+                String::from("Familie Muster"),
+                String::from("8332 Mustrikon"),
+            ],
+            country: None,
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        let template: MessageTemplate = "Hallo {nicknames} ({count}, {groups}, {roles}) {{{name}}}"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            template.render(&couvert()),
+            concat!(
+                "Hallo Focus, Levanzo und Pseudo ",
+                "(3, Skapande und Holon, Leiter und Teilnehmer) {Familie Muster}"
+            )
+        );
+    }
+
+    #[test]
+    fn test_unknown_placeholder() {
+        assert!("Hallo {vorname}".parse::<MessageTemplate>().is_err());
+        assert!("Hallo {nickname".parse::<MessageTemplate>().is_err());
+        assert!("Hallo }".parse::<MessageTemplate>().is_err());
+    }
+}
@@ -3,12 +3,25 @@
 /// An interface that allows internal functions to inform the user about something
 pub trait UserInteractor {
     fn on_download_finished(&self) {}
+    fn on_pages_fetched(&self, _endpoint_number: usize, _pages: usize) {}
+    fn on_telemetry_finished(&self, _result: Result<(), &dyn std::error::Error>) {}
     fn on_parsing_finished(&self) {}
     fn report_bad_address(&self, _broken_person: &dbparse::ReasonablePerson) {}
     fn on_pdf_generation_finished(&self) {}
     fn error_missing_config_file(&self, _filename: String) {}
     fn error_injecting_couverts(&self, _error: &dyn std::error::Error) {}
     fn error_fetching_auth_token(&self, _error: &std::io::Error) {}
+    fn error_dbparse(&self, _error: &dbparse::Error) {}
+    fn report_unknown_role(&self, _role_type: &str) {}
+    fn error_loading_roles(&self, _error: &dyn std::error::Error) {}
+    fn error_writing_file(&self, _filename: &str, _error: &dyn std::error::Error) {}
+    fn error_loading_group_priorities(&self, _error: &dyn std::error::Error) {}
+    fn error_loading_households(&self, _error: &dyn std::error::Error) {}
+    fn error_loading_exclusions(&self, _error: &dyn std::error::Error) {}
+    fn error_loading_layout(&self, _error: &dyn std::error::Error) {}
+    fn error_loading_font(&self, _path: &str, _reason: &str) {}
+    fn report_text_too_long(&self, _text: &str, _font_size: i64, _truncated: bool) {}
+    fn show_report(&self, _report: &str) {}
     fn interactively_get_auth_token(&self) -> Result<AuthTokenData, std::io::Error>;
     fn inform_user(&self, msg: &str) {
         println!("{}", msg);
@@ -23,6 +36,22 @@ impl UserInteractor for CliUi {
         println!("UI: Download Finished.");
     }
 
+    fn on_pages_fetched(&self, endpoint_number: usize, pages: usize) {
+        println!(
+            "UI: Endpoint {} delivered {} page{}.",
+            endpoint_number,
+            pages,
+            if pages == 1 { "" } else { "s" }
+        );
+    }
+
+    fn on_telemetry_finished(&self, result: Result<(), &dyn std::error::Error>) {
+        match result {
+            Ok(()) => println!("UI: Usage statistics sent."),
+            Err(e) => println!("UI: Could not send usage statistics ({}). Continuing anyway.", e),
+        }
+    }
+
     fn on_parsing_finished(&self) {
         println!("UI: Parsing Finished.");
     }
@@ -50,7 +79,7 @@ impl UserInteractor for CliUi {
         println!(
             r###"\
 UI: There was an error while trying to inject additional people:
-  {:?}
+  {}
   Perhaps there's a problem with inject_people.yaml?
 "###,
             error
@@ -68,6 +97,96 @@ UI: There was an error while trying to inject additional people:
         }
     }
 
+    fn error_dbparse(&self, error: &dbparse::Error) {
+        println!("UI: Error: {}", error);
+    }
+
+    fn report_unknown_role(&self, role_type: &str) {
+        println!(
+            "UI: Unknown role {:?}. It has been added to roles.yaml without a badge. Edit roles.yaml if it should get one.",
+            role_type
+        );
+    }
+
+    fn error_loading_roles(&self, error: &dyn std::error::Error) {
+        println!(
+            "UI: There was a problem with roles.yaml, using the default roles instead:
+  {}
+  Delete roles.yaml to have it regenerated.",
+            error
+        );
+    }
+
+    fn error_writing_file(&self, filename: &str, error: &dyn std::error::Error) {
+        println!(
+            "UI: {} could not be written, new entries are only used for this run:
+  {}",
+            filename, error
+        );
+    }
+
+    fn error_loading_group_priorities(&self, error: &dyn std::error::Error) {
+        println!(
+            "UI: There was a problem with group_priorities.yaml, using the default priorities instead:
+  {}
+  Delete group_priorities.yaml to have it regenerated.",
+            error
+        );
+    }
+
+    fn error_loading_households(&self, error: &dyn std::error::Error) {
+        println!(
+            "UI: There was a problem with households.yaml, using the default settings instead:
+  {}
+  Delete households.yaml to have it regenerated.",
+            error
+        );
+    }
+
+    fn error_loading_exclusions(&self, error: &dyn std::error::Error) {
+        println!(
+            "UI: There was a problem with exclude.yaml, nobody is excluded:
+  {}
+  Fix exclude.yaml, or delete it to have it regenerated.",
+            error
+        );
+    }
+
+    fn error_loading_layout(&self, error: &dyn std::error::Error) {
+        println!(
+            "UI: There was a problem with layout.yaml, the couverts get the default layout:
+  {}
+  Fix layout.yaml, or delete it to have it regenerated.",
+            error
+        );
+    }
+
+    fn error_loading_font(&self, path: &str, reason: &str) {
+        println!(
+            "UI: The font {} from layout.yaml could not be used, the bundled font is used instead:
+  {}",
+            path, reason
+        );
+    }
+
+    fn report_text_too_long(&self, text: &str, font_size: i64, truncated: bool) {
+        if truncated {
+            println!(
+                "UI: {:?} is too long for its place even in {}pt and has been cut off.",
+                text, font_size
+            );
+        } else {
+            println!(
+                "UI: {:?} is too long for its place and has been printed in {}pt.",
+                text, font_size
+            );
+        }
+    }
+
+    fn show_report(&self, report: &str) {
+        println!("UI: Report:\n{}", report);
+    }
+
     fn interactively_get_auth_token(&self) -> Result<AuthTokenData, std::io::Error> {
         use std::io::Write;
         print!("e-mail: ");